@@ -0,0 +1,2841 @@
+//! Task model and list logic for gottodo, kept free of terminal I/O so the
+//! binary in `main.rs` only has to drive it.
+
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use ratatui::style::Color;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Task {
+    /// Stable identity; 0 means "not yet assigned" in files from older versions.
+    #[serde(default)]
+    pub id: u64,
+    pub text: String,
+    pub done: bool,
+    #[serde(default)]
+    pub time_spent_secs: u64,
+    #[serde(default)]
+    pub pomodoros: u32,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    /// Time of day on `due` the task is due; without one it is due by the end of the day
+    #[serde(default)]
+    pub due_time: Option<NaiveTime>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Local>>,
+    /// Hidden from the main list until this time passes
+    #[serde(default)]
+    pub snoozed_until: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Expected effort, to compare against `time_spent_secs`
+    #[serde(default)]
+    pub estimate_mins: Option<u32>,
+    /// A heading that structures the list rather than something to do
+    #[serde(default)]
+    pub separator: bool,
+    /// Started but not finished; only meaningful while `done` is false
+    #[serde(default)]
+    pub in_progress: bool,
+    /// Remind every this many minutes while the app runs, until the task is done
+    #[serde(default)]
+    pub reminder_interval_mins: Option<u32>,
+    /// Ids of the tasks that have to be done before this one can start
+    #[serde(default)]
+    pub blocked_by: Vec<u64>,
+}
+
+/// Where a task is in todo → in progress → done, derived from `done` and
+/// `in_progress` so files without the latter read as todo or done.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Status {
+    Todo,
+    InProgress,
+    Done,
+}
+
+impl Task {
+    pub fn new(id: u64, text: &str) -> Self {
+        Task {
+            id,
+            text: text.to_string(),
+            done: false,
+            time_spent_secs: 0,
+            pomodoros: 0,
+            created_at: Some(Local::now()),
+            due: None,
+            due_time: None,
+            completed_at: None,
+            snoozed_until: None,
+            category: None,
+            estimate_mins: None,
+            separator: false,
+            in_progress: false,
+            reminder_interval_mins: None,
+            blocked_by: Vec::new(),
+        }
+    }
+
+    /// The moment the task falls due: its due time, or the last second of a
+    /// date-only due day.
+    pub fn due_at(&self) -> Option<NaiveDateTime> {
+        let end_of_day = NaiveTime::from_hms_opt(23, 59, 59).unwrap_or_default();
+        self.due.map(|due| due.and_time(self.due_time.unwrap_or(end_of_day)))
+    }
+
+    /// The due date in `date_format`, then ` HH:MM` when there is a due
+    /// time. With `DEFAULT_DATE_FORMAT` this is also what the due prompt takes.
+    pub fn due_label(&self, date_format: &str) -> Option<String> {
+        let due = self.due?;
+        let date = format_date(due, date_format).unwrap_or_else(|| due.to_string());
+        Some(match self.due_time {
+            Some(time) => format!("{} {}", date, time.format("%H:%M")),
+            None => date,
+        })
+    }
+
+    /// Flips the done flag, stamping or clearing the completion time.
+    pub fn toggle_done(&mut self, now: DateTime<Local>) {
+        self.done = !self.done;
+        self.in_progress = false;
+        self.completed_at = self.done.then_some(now);
+    }
+
+    pub fn status(&self) -> Status {
+        match (self.done, self.in_progress) {
+            (true, _) => Status::Done,
+            (false, true) => Status::InProgress,
+            (false, false) => Status::Todo,
+        }
+    }
+
+    /// Moves on to the next status, going round from done back to todo.
+    pub fn cycle_status(&mut self, now: DateTime<Local>) {
+        match self.status() {
+            Status::Todo => self.in_progress = true,
+            Status::InProgress | Status::Done => self.toggle_done(now),
+        }
+    }
+}
+
+/// Makes the task at `index` the one task in progress, reopening it if it
+/// was done and moving every other in-progress task back to todo. Returns
+/// how many tasks were moved back.
+pub fn start_only(tasks: &mut [Task], index: usize, now: DateTime<Local>) -> usize {
+    let mut cleared = 0;
+    for (i, task) in tasks.iter_mut().enumerate() {
+        if i != index && task.in_progress {
+            task.in_progress = false;
+            cleared += 1;
+        }
+    }
+    if tasks[index].done {
+        tasks[index].toggle_done(now);
+    }
+    tasks[index].in_progress = true;
+    cleared
+}
+
+/// The result of loading a store.
+pub struct Loaded {
+    pub tasks: Vec<Task>,
+    /// Set when the stored data couldn't be parsed and only part (or none)
+    /// of it was recovered.
+    pub warning: Option<String>,
+}
+
+/// Where the task list is loaded from and saved to.
+pub trait TaskStore {
+    /// Loads every task; a missing store yields an empty list.
+    fn load(&self) -> Loaded;
+    /// The highest id handed out so far, counting tasks since deleted, so
+    /// `next_id` never gives a new task an old task's id.
+    fn last_id(&self) -> u64;
+    fn save(&mut self, tasks: &[Task], last_id: u64) -> std::io::Result<()>;
+}
+
+/// Tasks kept as pretty-printed JSON in a file.
+pub struct FileStore {
+    path: PathBuf,
+}
+
+/// What reading a `FileStore` found, before anything is done about it.
+enum Contents {
+    Missing,
+    Unreadable(std::io::Error),
+    Tasks(Vec<Task>),
+    /// The raw text, for the backup, and why it didn't parse
+    Corrupt(String, serde_json::Error),
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileStore { path: path.into() }
+    }
+
+    /// Explains why the path can never hold the task list: it is a
+    /// directory, a symlink that loops or points nowhere, or somewhere no
+    /// file can be made (say, under a regular file). A missing file is fine,
+    /// since the first load creates it.
+    pub fn check(&self) -> Result<(), String> {
+        let path = self.path.display();
+        let link = match std::fs::symlink_metadata(&self.path) {
+            Ok(link) => link,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(format!("Can't use {}: {}", path, err)),
+        };
+        match std::fs::metadata(&self.path) {
+            Ok(meta) if meta.is_dir() => Err(format!("{} is a directory; move it aside so the task list can be created", path)),
+            Ok(_) => Ok(()),
+            Err(err) if link.file_type().is_symlink() => {
+                let target = std::fs::read_link(&self.path).map_or_else(|_| "?".to_string(), |target| target.display().to_string());
+                Err(format!("{} is a symlink to {} that can't be followed ({}); fix or remove the link", path, target, err))
+            }
+            Err(err) => Err(format!("Can't use {}: {}", path, err)),
+        }
+    }
+
+    /// Loads the tasks like `load` but leaves the file alone: a missing file
+    /// isn't created and a corrupt one isn't backed up. For readers running
+    /// beside the app, such as `--serve`.
+    pub fn read(&self) -> Loaded {
+        match self.contents() {
+            Contents::Missing => Loaded { tasks: Vec::new(), warning: None },
+            Contents::Unreadable(err) => self.unreadable(err),
+            Contents::Tasks(tasks) => Loaded { tasks, warning: None },
+            Contents::Corrupt(contents, err) => {
+                let tasks = recover_tasks(&contents);
+                Loaded {
+                    warning: Some(format!("{} is corrupt ({}); recovered {} task(s)", self.path.display(), err, tasks.len())),
+                    tasks,
+                }
+            }
+        }
+    }
+
+    fn contents(&self) -> Contents {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) if contents.trim().is_empty() => Contents::Tasks(Vec::new()),
+            Ok(contents) => match serde_json::from_str(&contents) {
+                Ok(tasks) => Contents::Tasks(tasks),
+                Err(err) => Contents::Corrupt(contents, err),
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Contents::Missing,
+            Err(err) => Contents::Unreadable(err),
+        }
+    }
+
+    /// The path beside the file with `suffix` added, as in `todos.json.tmp`.
+    fn beside(&self, suffix: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(suffix);
+        PathBuf::from(path)
+    }
+
+    fn unreadable(&self, err: std::io::Error) -> Loaded {
+        Loaded {
+            tasks: Vec::new(),
+            warning: Some(format!("Couldn't read {}: {}", self.path.display(), err)),
+        }
+    }
+}
+
+/// Reads the tasks at the start of a JSON array, stopping at the first
+/// element that doesn't parse (or where a truncated file ends).
+pub fn recover_tasks(contents: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let Some(mut rest) = contents.trim_start().strip_prefix('[') else {
+        return tasks;
+    };
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Task>();
+        match stream.next() {
+            Some(Ok(task)) => {
+                tasks.push(task);
+                rest = &rest[stream.byte_offset()..];
+            }
+            _ => return tasks,
+        }
+    }
+}
+
+impl TaskStore for FileStore {
+    /// An unparseable file is copied to `<path>.corrupt` before anything can
+    /// overwrite it, and whatever leading tasks still parse are kept.
+    fn load(&self) -> Loaded {
+        match self.contents() {
+            Contents::Missing => {
+                let _ = std::fs::File::create(&self.path);
+                Loaded { tasks: Vec::new(), warning: None }
+            }
+            Contents::Unreadable(err) => self.unreadable(err),
+            Contents::Tasks(tasks) => Loaded { tasks, warning: None },
+            Contents::Corrupt(contents, err) => {
+                let backup = self.beside(".corrupt");
+                let tasks = recover_tasks(&contents);
+                let saved = match std::fs::write(&backup, &contents) {
+                    Ok(()) => format!("backed up to {}", backup.display()),
+                    Err(backup_err) => format!("backup to {} failed: {}", backup.display(), backup_err),
+                };
+                Loaded {
+                    warning: Some(format!(
+                        "{} is corrupt ({}); {}, recovered {} task(s)",
+                        self.path.display(),
+                        err,
+                        saved,
+                        tasks.len()
+                    )),
+                    tasks,
+                }
+            }
+        }
+    }
+
+    /// Kept in `<path>.last_id`, beside the list rather than in it so the
+    /// file stays a plain array of tasks.
+    fn last_id(&self) -> u64 {
+        std::fs::read_to_string(self.beside(".last_id")).ok().and_then(|text| text.trim().parse().ok()).unwrap_or(0)
+    }
+
+    /// Writes `<path>.tmp` and renames it over the file once it is on disk,
+    /// so a crash mid-save leaves the old list rather than half a new one,
+    /// and a save that succeeded survives a power loss straight after.
+    /// `<path>.last_id` is only written once the newest task has been
+    /// deleted; until then the highest id in the list says the same.
+    fn save(&mut self, tasks: &[Task], last_id: u64) -> std::io::Result<()> {
+        replace_file(&self.path, &self.beside(".tmp"), &serde_json::to_vec_pretty(tasks)?)?;
+        if last_id > tasks.iter().map(|task| task.id).max().unwrap_or(0) {
+            replace_file(&self.beside(".last_id"), &self.beside(".last_id.tmp"), last_id.to_string().as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `contents` to `temp`, syncs it and renames it over `path`.
+fn replace_file(path: &Path, temp: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(temp, path)
+}
+
+/// Tasks held in memory only, for tests and throwaway sessions.
+#[derive(Default)]
+pub struct MemoryStore {
+    pub tasks: Vec<Task>,
+    pub last_id: u64,
+}
+
+impl TaskStore for MemoryStore {
+    fn load(&self) -> Loaded {
+        Loaded { tasks: self.tasks.clone(), warning: None }
+    }
+
+    fn last_id(&self) -> u64 {
+        self.last_id
+    }
+
+    fn save(&mut self, tasks: &[Task], last_id: u64) -> std::io::Result<()> {
+        self.tasks = tasks.to_vec();
+        self.last_id = last_id;
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortKey {
+    Alpha,
+    DoneLast,
+    Due,
+}
+
+/// How often `reset_period` unchecks the whole list.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ResetPeriod {
+    Daily,
+    Weekly,
+}
+
+/// Agenda headings, in display order.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum AgendaBucket {
+    Overdue,
+    Today,
+    ThisWeek,
+    Later,
+    NoDate,
+}
+
+impl SortKey {
+    /// The name `:sort` takes for this key.
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Alpha => "alpha",
+            SortKey::DoneLast => "done",
+            SortKey::Due => "due",
+        }
+    }
+}
+
+impl AgendaBucket {
+    pub fn title(self) -> &'static str {
+        match self {
+            AgendaBucket::Overdue => "Overdue",
+            AgendaBucket::Today => "Today",
+            AgendaBucket::ThisWeek => "This week",
+            AgendaBucket::Later => "Later",
+            AgendaBucket::NoDate => "No date",
+        }
+    }
+}
+
+/// One line of the rendered list: a heading that can't be selected, a task
+/// index with the char positions its text matched the search on, or a tag
+/// heading in the grouped view with its task count and whether it is
+/// collapsed.
+pub enum Row {
+    Header(String),
+    Task(usize, Vec<usize>),
+    Group(String, usize, bool),
+}
+
+/// The grouped view's heading for tasks without a `#tag`.
+pub const UNTAGGED: &str = "Untagged";
+
+/// A find-and-replace over task text, from `:s/find/replace/[i]`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Replacement {
+    pub find: String,
+    pub replace: String,
+    pub ignore_case: bool,
+}
+
+/// A parsed `:` command.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Replace(Replacement),
+    /// Show the list sorted, or back in its manual order with None
+    Sort(Option<SortKey>),
+    ClearDone,
+    ClearAll,
+    ExportOutline(String),
+    /// Toggle the task shown at this 1-based position
+    Toggle(usize),
+    Export(String),
+    Help,
+    Quit,
+}
+
+/// A request read from the `--listen` control socket.
+#[derive(Debug, PartialEq)]
+pub enum RemoteCommand {
+    Add(String),
+    List,
+    Toggle(u64),
+}
+
+/// One change to the list, as recorded in the event log.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum TaskEvent {
+    Add { task: Task },
+    Update { task: Task },
+    Delete { task: Task },
+    /// The full id order after tasks were moved around
+    Reorder { ids: Vec<u64> },
+}
+
+/// A line of `events.jsonl`.
+#[derive(Serialize, Deserialize)]
+pub struct LoggedEvent {
+    pub at: DateTime<Local>,
+    #[serde(flatten)]
+    pub event: TaskEvent,
+}
+
+/// The events that turn `before` into `after`, matching tasks by id.
+pub fn diff_events(before: &[Task], after: &[Task]) -> Vec<TaskEvent> {
+    let mut events = Vec::new();
+    for task in before {
+        if index_of(after, task.id).is_none() {
+            events.push(TaskEvent::Delete { task: task.clone() });
+        }
+    }
+    for task in after {
+        match index_of(before, task.id) {
+            None => events.push(TaskEvent::Add { task: task.clone() }),
+            Some(index) if before[index] != *task => events.push(TaskEvent::Update { task: task.clone() }),
+            Some(_) => {}
+        }
+    }
+    // Replaying the events so far keeps the surviving order and appends adds;
+    // anything else needs the order spelled out
+    let replayed = before
+        .iter()
+        .filter(|task| index_of(after, task.id).is_some())
+        .chain(after.iter().filter(|task| index_of(before, task.id).is_none()))
+        .map(|task| task.id);
+    if !replayed.eq(after.iter().map(|task| task.id)) {
+        events.push(TaskEvent::Reorder { ids: after.iter().map(|task| task.id).collect() });
+    }
+    events
+}
+
+/// Rebuilds the list by applying `events` in order to an empty one.
+pub fn replay_events(events: impl IntoIterator<Item = TaskEvent>) -> Vec<Task> {
+    let mut tasks: Vec<Task> = Vec::new();
+    for event in events {
+        match event {
+            TaskEvent::Add { task } | TaskEvent::Update { task } => match index_of(&tasks, task.id) {
+                Some(index) => tasks[index] = task,
+                None => tasks.push(task),
+            },
+            TaskEvent::Delete { task } => tasks.retain(|existing| existing.id != task.id),
+            TaskEvent::Reorder { ids } => {
+                tasks.sort_by_key(|task| ids.iter().position(|&id| id == task.id).unwrap_or(usize::MAX));
+            }
+        }
+    }
+    tasks
+}
+
+/// Reads the events in a log file, skipping lines that don't parse (such as a
+/// last line cut short by a crash).
+pub fn read_events(path: &Path) -> std::io::Result<Vec<TaskEvent>> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<LoggedEvent>(line).ok())
+        .map(|logged| logged.event)
+        .collect())
+}
+
+/// An append-only `events.jsonl` writer that logs whatever changed since it
+/// last saw the list.
+pub struct EventLog {
+    file: std::fs::File,
+    /// The list as the log would replay it
+    snapshot: Vec<Task>,
+}
+
+impl EventLog {
+    /// Opens (or creates) the log and replays it, so the first `record`
+    /// also captures changes made while logging was off.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let snapshot = match read_events(path) {
+            Ok(events) => replay_events(events),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(EventLog { file, snapshot })
+    }
+
+    /// Appends one line per change, each synced to disk before returning.
+    pub fn record(&mut self, tasks: &[Task]) -> std::io::Result<()> {
+        if self.snapshot == tasks {
+            return Ok(());
+        }
+        let now = Local::now();
+        for event in diff_events(&self.snapshot, tasks) {
+            let mut line = serde_json::to_string(&LoggedEvent { at: now, event })?;
+            line.push('\n');
+            self.file.write_all(line.as_bytes())?;
+            self.file.sync_data()?;
+        }
+        self.snapshot = tasks.to_vec();
+        Ok(())
+    }
+}
+
+/// The id to give the next new task, recorded in `last_id`. Ids only go up,
+/// so one that belonged to a deleted task is never handed out again.
+pub fn next_id(tasks: &[Task], last_id: &mut u64) -> u64 {
+    let id = tasks.iter().map(|task| task.id).max().unwrap_or(0).max(*last_id) + 1;
+    *last_id = id;
+    id
+}
+
+/// Gives every task loaded without an id (older files) a fresh one.
+/// Returns whether anything changed so the caller can persist the migration.
+pub fn assign_missing_ids(tasks: &mut [Task], last_id: &mut u64) -> bool {
+    let mut changed = false;
+    for i in 0..tasks.len() {
+        if tasks[i].id == 0 {
+            tasks[i].id = next_id(tasks, last_id);
+            changed = true;
+        }
+    }
+    changed
+}
+
+pub fn index_of(tasks: &[Task], id: u64) -> Option<usize> {
+    tasks.iter().position(|task| task.id == id)
+}
+
+/// The label of a separator typed as a line starting with `--`, with the
+/// dashes around it dropped: `--- Work ---` is `Work`, and `--` alone is a
+/// blank separator.
+pub fn separator_label(text: &str) -> Option<&str> {
+    let text = text.trim();
+    text.starts_with("--").then(|| text.trim_matches('-').trim())
+}
+
+/// Appends a new task, or a separator when the text starts with `--`, and
+/// returns its id.
+pub fn add_task(tasks: &mut Vec<Task>, last_id: &mut u64, text: &str) -> u64 {
+    let id = next_id(tasks, last_id);
+    let task = match separator_label(text) {
+        Some(label) => Task { separator: true, ..Task::new(id, label) },
+        None => Task::new(id, text),
+    };
+    tasks.push(task);
+    id
+}
+
+/// Whether some task already has exactly this (trimmed) text. Separators are
+/// only compared with separators, by label.
+pub fn is_duplicate(tasks: &[Task], text: &str, ignore_case: bool) -> bool {
+    let (separator, text) = match separator_label(text) {
+        Some(label) => (true, label),
+        None => (false, text.trim()),
+    };
+    tasks.iter().filter(|task| task.separator == separator).any(|task| {
+        let existing = task.text.trim();
+        if ignore_case { existing.to_lowercase() == text.to_lowercase() } else { existing == text }
+    })
+}
+
+/// The task texts in a plain text file: one per line, trimmed, skipping
+/// blank lines and `#` comments.
+pub fn parse_txt_tasks(contents: &str) -> Vec<&str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+/// A run of outline items, under the top-level line they were indented below.
+#[derive(Debug, PartialEq)]
+pub struct OutlineSection<'a> {
+    /// `None` for top-level items that have nothing indented under them
+    pub heading: Option<&'a str>,
+    pub items: Vec<&'a str>,
+}
+
+/// Reads an indented outline, where a tab counts as two spaces. Tasks don't
+/// nest, so a top-level line with lines indented below it becomes a section
+/// heading and everything below it, however deep or unevenly indented, an
+/// item of that section. Blank lines and `#` comments are skipped.
+pub fn parse_outline(contents: &str) -> Vec<OutlineSection<'_>> {
+    let lines: Vec<(usize, &str)> = contents
+        .lines()
+        .map(|line| {
+            let text = line.trim_start();
+            let indent: usize = line[..line.len() - text.len()].chars().map(|c| if c == '\t' { 2 } else { 1 }).sum();
+            (indent, text.trim_end())
+        })
+        .filter(|(_, text)| !text.is_empty() && !text.starts_with('#'))
+        .collect();
+    let mut sections: Vec<OutlineSection> = Vec::new();
+    for (i, &(indent, text)) in lines.iter().enumerate() {
+        let nested = indent > 0;
+        let has_children = lines.get(i + 1).is_some_and(|&(next, _)| next > 0);
+        match sections.last_mut() {
+            Some(section) if nested || (section.heading.is_none() && !has_children) => section.items.push(text),
+            _ if has_children && !nested => sections.push(OutlineSection { heading: Some(text), items: Vec::new() }),
+            _ => sections.push(OutlineSection { heading: None, items: vec![text] }),
+        }
+    }
+    sections
+}
+
+/// Appends the outline as separator headings with their items below. Items
+/// already in the list are skipped as in `merge_tasks`, and so is a heading
+/// left with nothing new under it. Returns how many tasks were added and how
+/// many were skipped.
+pub fn merge_outline(tasks: &mut Vec<Task>, last_id: &mut u64, sections: &[OutlineSection]) -> (usize, usize) {
+    let (mut added, mut skipped) = (0, 0);
+    for section in sections {
+        let mut items: Vec<&str> = Vec::new();
+        for &text in &section.items {
+            if !items.contains(&text) && !is_duplicate(tasks, text, false) {
+                items.push(text);
+            }
+        }
+        skipped += section.items.len() - items.len();
+        if items.is_empty() {
+            continue;
+        }
+        // Top-level items would otherwise land in the section above them
+        let under_heading = tasks.iter().rev().find(|task| task.separator).is_some_and(|task| !task.text.is_empty());
+        let heading = section.heading.or(under_heading.then_some(""));
+        if let Some(heading) = heading {
+            let id = next_id(tasks, last_id);
+            tasks.push(Task { separator: true, ..Task::new(id, heading) });
+        }
+        for text in items {
+            let id = next_id(tasks, last_id);
+            tasks.push(Task::new(id, text));
+            added += 1;
+        }
+    }
+    (added, skipped)
+}
+
+/// Writes the list as an outline `parse_outline` reads back: separator labels
+/// at the left margin with the tasks after them indented two spaces. A bare
+/// rule ends a section without a line of its own.
+pub fn export_outline(tasks: &[Task]) -> String {
+    let mut lines = String::new();
+    let mut in_section = false;
+    for task in tasks {
+        if task.separator {
+            in_section = !task.text.is_empty();
+            if in_section {
+                lines.push_str(&format!("{}\n", task.text));
+            }
+        } else {
+            lines.push_str(&format!("{}{}\n", if in_section { "  " } else { "" }, task.text));
+        }
+    }
+    lines
+}
+
+/// Appends a task for each text not already in the list (exact match after
+/// trimming, including texts added earlier in the same call). Returns how
+/// many were added and how many were skipped as duplicates.
+pub fn merge_tasks(tasks: &mut Vec<Task>, last_id: &mut u64, texts: &[&str]) -> (usize, usize) {
+    let mut added = 0;
+    for text in texts {
+        if !is_duplicate(tasks, text, false) {
+            add_task(tasks, last_id, text.trim());
+            added += 1;
+        }
+    }
+    (added, texts.len() - added)
+}
+
+/// `text` cut to `max_chars` characters, the last one an ellipsis, or None
+/// when it already fits.
+pub fn truncate_chars(text: &str, max_chars: usize) -> Option<String> {
+    if text.chars().count() <= max_chars {
+        return None;
+    }
+    let mut cut: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    cut.truncate(cut.trim_end().len());
+    cut.push('…');
+    Some(cut)
+}
+
+/// The `#tag` words in a task's text, without trailing punctuation, sorted
+/// and without repeats.
+pub fn task_tags(text: &str) -> Vec<String> {
+    let tags: BTreeSet<String> = text
+        .split_whitespace()
+        .map(|word| word.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+        .filter(|word| word.len() > 1 && word.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    tags.into_iter().collect()
+}
+
+/// The `#tag` words used across task texts, sorted and without repeats.
+pub fn all_tags(tasks: &[Task]) -> Vec<String> {
+    let tags: BTreeSet<String> = tasks.iter().flat_map(|task| task_tags(&task.text)).collect();
+    tags.into_iter().collect()
+}
+
+/// `input` with its last word, when that starts with `#`, replaced by the
+/// `n`th (wrapping) of `tags` that starts with `prefix`, ignoring case.
+/// None when the last word isn't a tag or no tag matches.
+pub fn complete_tag(input: &str, tags: &[String], prefix: &str, n: usize) -> Option<String> {
+    let word = input.rsplit(char::is_whitespace).next().unwrap_or("");
+    if !word.starts_with('#') {
+        return None;
+    }
+    let prefix = prefix.to_lowercase();
+    let matches: Vec<&String> = tags.iter().filter(|tag| tag.to_lowercase().starts_with(&prefix)).collect();
+    let tag = matches.get(n % matches.len().max(1))?;
+    Some(format!("{}{}", &input[..input.len() - word.len()], tag))
+}
+
+/// How a stretch of task text is emphasised by inline markup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emphasis {
+    Plain,
+    Bold,
+    Italic,
+    Code,
+}
+
+/// The chars of `text` left once inline markup is taken out, as their char
+/// index in `text` and emphasis. `*bold*`, `_italic_` and `` `code` `` need
+/// a closing marker and no space just inside either one; `*` and `_` also
+/// only count at word edges, so `snake_case` and `2*3*4` stay literal.
+/// Markup doesn't nest and anything unmatched is kept as typed.
+pub fn parse_markup(text: &str) -> Vec<(usize, Emphasis)> {
+    let chars: Vec<char> = text.chars().collect();
+    let marker = |c: char| match c {
+        '*' => Some(Emphasis::Bold),
+        '_' => Some(Emphasis::Italic),
+        '`' => Some(Emphasis::Code),
+        _ => None,
+    };
+    let mut shown = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let word_edge = |j: Option<usize>| c == '`' || j.and_then(|j| chars.get(j)).is_none_or(|c| !c.is_alphanumeric());
+        let close = marker(c)
+            .filter(|_| word_edge(i.checked_sub(1)))
+            .filter(|_| chars.get(i + 1).is_some_and(|&next| next != c && !next.is_whitespace()))
+            .and_then(|emphasis| {
+                let j = (i + 2..chars.len())
+                    .find(|&j| chars[j] == c && !chars[j - 1].is_whitespace() && word_edge(Some(j + 1)))?;
+                Some((j, emphasis))
+            });
+        match close {
+            Some((j, emphasis)) => {
+                shown.extend((i + 1..j).map(|k| (k, emphasis)));
+                i = j + 1;
+            }
+            None => {
+                shown.push((i, Emphasis::Plain));
+                i += 1;
+            }
+        }
+    }
+    shown
+}
+
+/// Moves the task at `index` to the start (or end) of the list, returning the
+/// same old→new remap as `apply_order`.
+pub fn move_to_end(tasks: &mut Vec<Task>, index: usize, to_top: bool) -> Vec<Option<usize>> {
+    let rest = (0..tasks.len()).filter(|&i| i != index);
+    let order: Vec<usize> = if to_top {
+        std::iter::once(index).chain(rest).collect()
+    } else {
+        rest.chain(std::iter::once(index)).collect()
+    };
+    apply_order(tasks, &order)
+}
+
+/// Removes the task at `index`, returning the same old→new remap as `apply_order`.
+pub fn delete_task(tasks: &mut Vec<Task>, index: usize) -> Vec<Option<usize>> {
+    let order: Vec<usize> = (0..tasks.len()).filter(|&i| i != index).collect();
+    apply_order(tasks, &order)
+}
+
+/// Removes every task whose id is in `ids`, returning the same old→new remap as `apply_order`.
+pub fn delete_tasks(tasks: &mut Vec<Task>, ids: &HashSet<u64>) -> Vec<Option<usize>> {
+    let order: Vec<usize> = (0..tasks.len()).filter(|&i| !ids.contains(&tasks[i].id)).collect();
+    apply_order(tasks, &order)
+}
+
+/// Moves every task whose id is in `ids` to the start (or end) of the list,
+/// keeping their order among themselves, and returns the `apply_order` remap.
+pub fn move_all_to_end(tasks: &mut Vec<Task>, ids: &HashSet<u64>, to_top: bool) -> Vec<Option<usize>> {
+    let (mut order, rest): (Vec<usize>, Vec<usize>) = (0..tasks.len()).partition(|&i| ids.contains(&tasks[i].id) == to_top);
+    order.extend(rest);
+    apply_order(tasks, &order)
+}
+
+/// Parses the text typed after `:` into a command.
+pub fn parse_command(input: &str) -> Result<Command, String> {
+    if let Some(body) = input.trim_start().strip_prefix("s/") {
+        return parse_substitution(body).map(Command::Replace);
+    }
+    let mut words = input.split_whitespace();
+    let name = words.next().ok_or("Empty command")?;
+    let rest: Vec<&str> = words.collect();
+    match (name, rest.as_slice()) {
+        ("sort", ["none" | "manual"]) => Ok(Command::Sort(None)),
+        ("sort", [key]) => parse_sort_key(key)
+            .map(|key| Command::Sort(Some(key)))
+            .ok_or_else(|| format!("Unknown sort key '{}' (use alpha, done, due or none)", key)),
+        ("sort", _) => Err("Usage: sort <alpha|done|due|none>".to_string()),
+        ("clear-done", []) => Ok(Command::ClearDone),
+        ("clear-all", []) => Ok(Command::ClearAll),
+        ("toggle" | "done", [number]) => number
+            .parse()
+            .ok()
+            .filter(|&number| number > 0)
+            .map(Command::Toggle)
+            .ok_or_else(|| format!("Not a task number: '{}'", number)),
+        ("toggle" | "done", _) => Err("Usage: toggle <number>".to_string()),
+        ("export", []) => Err("Usage: export <path>".to_string()),
+        ("export", path) => Ok(Command::Export(path.join(" "))),
+        ("export-outline", []) => Err("Usage: export-outline <path>".to_string()),
+        ("export-outline", path) => Ok(Command::ExportOutline(path.join(" "))),
+        ("help", []) => Ok(Command::Help),
+        ("q" | "quit", []) => Ok(Command::Quit),
+        ("clear-done" | "clear-all" | "help" | "q" | "quit", _) => Err(format!("{} takes no arguments", name)),
+        (other, _) => Err(format!("Unknown command: {}", other)),
+    }
+}
+
+/// Parses one line sent to the control socket: `add <text>`, `list` or
+/// `toggle <id>`.
+pub fn parse_remote_command(line: &str) -> Result<RemoteCommand, String> {
+    let line = line.trim();
+    let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    match (name, rest) {
+        ("add", "") => Err("usage: add <text>".to_string()),
+        ("add", text) => Ok(RemoteCommand::Add(text.to_string())),
+        ("list", "") => Ok(RemoteCommand::List),
+        ("toggle", id) => id
+            .parse()
+            .map(RemoteCommand::Toggle)
+            .map_err(|_| "usage: toggle <id>".to_string()),
+        ("", _) => Err("empty command".to_string()),
+        (other, _) => Err(format!("unknown command: {}", other)),
+    }
+}
+
+/// Looks up a sort key by the name used in `:sort` and `default_sort`.
+pub fn parse_sort_key(name: &str) -> Option<SortKey> {
+    match name {
+        "alpha" => Some(SortKey::Alpha),
+        "done" | "done-last" => Some(SortKey::DoneLast),
+        "due" => Some(SortKey::Due),
+        _ => None,
+    }
+}
+
+/// The sort the list opens with: one picked in the app and `saved` with the
+/// preferences, else `default_sort` from the config. `none` is the list's
+/// own order either way, and a saved name that isn't a sort key is ignored.
+pub fn initial_sort(saved: Option<&str>, default_sort: &str) -> Option<SortKey> {
+    saved
+        .and_then(|name| if name == "none" { Some(None) } else { parse_sort_key(name).map(Some) })
+        .unwrap_or_else(|| parse_sort_key(default_sort))
+}
+
+/// Parses the `find/replace/flags` part of `s/find/replace/flags`. A `\/`
+/// stands for a literal slash; the only flag is `i` for case-insensitive.
+pub fn parse_substitution(body: &str) -> Result<Replacement, String> {
+    let mut parts = vec![String::new()];
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.clone().next() == Some('/') => {
+                chars.next();
+                parts.last_mut().unwrap().push('/');
+            }
+            '/' => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    let usage = "Usage: s/find/replace/ (add i for case-insensitive)".to_string();
+    let (find, replace, flags) = match parts.as_slice() {
+        [find, replace] => (find, replace, ""),
+        [find, replace, flags] => (find, replace, flags.as_str()),
+        _ => return Err(usage),
+    };
+    if find.is_empty() {
+        return Err(usage);
+    }
+    match flags {
+        "" | "i" => Ok(Replacement {
+            find: find.clone(),
+            replace: replace.clone(),
+            ignore_case: flags == "i",
+        }),
+        other => Err(format!("Unknown flag '{}' (only i is supported)", other)),
+    }
+}
+
+/// Byte range of the first match of `needle` in `haystack` at or after `from`,
+/// comparing lowercased chars when `ignore_case` is set.
+pub fn find_match(haystack: &str, needle: &str, from: usize, ignore_case: bool) -> Option<(usize, usize)> {
+    if !ignore_case {
+        return haystack[from..].find(needle).map(|start| (from + start, from + start + needle.len()));
+    }
+    let same = |a: char, b: char| a.to_lowercase().eq(b.to_lowercase());
+    haystack[from..].char_indices().find_map(|(offset, _)| {
+        let start = from + offset;
+        let mut rest = haystack[start..].char_indices();
+        let mut end = start;
+        for n in needle.chars() {
+            let (i, h) = rest.next()?;
+            if !same(h, n) {
+                return None;
+            }
+            end = start + i + h.len_utf8();
+        }
+        Some((start, end))
+    })
+}
+
+/// Applies `replacement` to every match in `text`; `None` if nothing matched.
+pub fn replace_text(text: &str, replacement: &Replacement) -> Option<String> {
+    let mut result = String::new();
+    let mut position = 0;
+    let mut matched = false;
+    while let Some((start, end)) = find_match(text, &replacement.find, position, replacement.ignore_case) {
+        result.push_str(&text[position..start]);
+        result.push_str(&replacement.replace);
+        position = end;
+        matched = true;
+    }
+    if !matched {
+        return None;
+    }
+    result.push_str(&text[position..]);
+    Some(result)
+}
+
+/// Indices of `tasks` in the order `key` sorts them; the sort is stable.
+pub fn sorted_order(tasks: &[Task], key: SortKey) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..tasks.len()).collect();
+    match key {
+        SortKey::Alpha => order.sort_by_cached_key(|&i| tasks[i].text.to_lowercase()),
+        SortKey::DoneLast => order.sort_by_key(|&i| tasks[i].done),
+        SortKey::Due => order.sort_by_key(|&i| (tasks[i].due.is_none(), tasks[i].due_at())),
+    }
+    order
+}
+
+/// ISO `YYYY-MM-DD`, how dates are shown unless `date_format` says otherwise.
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// `date` in a strftime-style `pattern`, or None when the pattern is invalid
+/// or asks for something a date doesn't have, like the hour.
+pub fn format_date(date: NaiveDate, pattern: &str) -> Option<String> {
+    use std::fmt::Write as _;
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(pattern)).ok()?;
+    Some(formatted)
+}
+
+/// Parses a date typed at a prompt: `YYYY-MM-DD`, `today`, `tomorrow`, or an
+/// offset like `+3` / `+3d` / `+2w`. Empty input means "no date".
+pub fn parse_date_input(input: &str, today: NaiveDate) -> Result<Option<NaiveDate>, String> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let offset_days = match input.as_str() {
+        "today" => Some(0),
+        "tomorrow" => Some(1),
+        other => other.strip_prefix('+').and_then(|offset| {
+            let (number, unit) = match offset.strip_suffix('w') {
+                Some(weeks) => (weeks, 7),
+                None => (offset.strip_suffix('d').unwrap_or(offset), 1),
+            };
+            number.parse::<u64>().ok().map(|n| n * unit)
+        }),
+    };
+    match offset_days {
+        Some(days) => today.checked_add_days(Days::new(days)).map(Some).ok_or_else(|| "Date out of range".to_string()),
+        None => NaiveDate::parse_from_str(&input, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| format!("Not a date: '{}' (try YYYY-MM-DD, today, tomorrow or +3d)", input)),
+    }
+}
+
+/// Parses the due prompt: anything `parse_date_input` accepts, optionally
+/// followed by a time as `HH:MM`, or a time alone for today. Empty input
+/// means "no due date".
+pub fn parse_due_input(input: &str, today: NaiveDate) -> Result<Option<(NaiveDate, Option<NaiveTime>)>, String> {
+    let input = input.trim();
+    let parse_time = |text: &str| NaiveTime::parse_from_str(text, "%H:%M").ok();
+    if let Some(time) = parse_time(input) {
+        return Ok(Some((today, Some(time))));
+    }
+    if let Some((date, time)) = input.rsplit_once(char::is_whitespace)
+        && let Some(time) = parse_time(time)
+    {
+        return Ok(parse_date_input(date, today)?.map(|date| (date, Some(time))));
+    }
+    Ok(parse_date_input(input, today)?.map(|date| (date, None)))
+}
+
+/// Parses an effort estimate: minutes as `90` or `90m`, or hours as `2h` or
+/// `1h30m`. Empty input means "no estimate".
+pub fn parse_minutes(input: &str) -> Result<Option<u32>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || format!("Not a duration: '{}' (use 90, 90m, 2h or 1h30m)", input);
+    let (hours, minutes) = match input.split_once('h') {
+        Some((hours, minutes)) => (hours, minutes),
+        None => ("0", input),
+    };
+    let minutes = minutes.strip_suffix('m').unwrap_or(minutes);
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = if minutes.is_empty() { 0 } else { minutes.parse().map_err(|_| invalid())? };
+    hours.checked_mul(60).and_then(|mins| mins.checked_add(minutes)).map(Some).ok_or_else(invalid)
+}
+
+/// When a reminder last due at `due` goes off next: the first point after
+/// `now` on its `interval_mins` grid, so one missed while the app was busy or
+/// suspended fires once rather than once per missed interval.
+pub fn next_reminder(due: DateTime<Local>, interval_mins: u32, now: DateTime<Local>) -> DateTime<Local> {
+    let interval = chrono::Duration::minutes(interval_mins.max(1) as i64);
+    if now < due {
+        return due;
+    }
+    let missed = (now - due).num_seconds() / interval.num_seconds();
+    due + interval * (missed as i32 + 1)
+}
+
+/// Parses a snooze prompt: `+Nh` snoozes for N hours, anything
+/// `parse_date_input` accepts snoozes until the start of that day, and empty
+/// input wakes the task up.
+pub fn parse_snooze_input(input: &str, now: DateTime<Local>) -> Result<Option<DateTime<Local>>, String> {
+    if let Some(hours) = input.trim().strip_prefix('+').and_then(|offset| offset.strip_suffix('h')) {
+        let hours: i64 = hours.parse().map_err(|_| format!("Not a number of hours: '{}'", hours))?;
+        return Ok(Some(now + chrono::Duration::hours(hours)));
+    }
+    match parse_date_input(input, now.date_naive())? {
+        None => Ok(None),
+        Some(date) => date
+            .and_hms_opt(0, 0, 0)
+            .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+            .map(Some)
+            .ok_or_else(|| "Date out of range".to_string()),
+    }
+}
+
+/// Counts for the statistics summary.
+pub struct Stats {
+    pub total: usize,
+    pub done: usize,
+    pub overdue: usize,
+    pub in_progress: usize,
+    pub completed_today: usize,
+    pub completed_this_week: usize,
+    /// Summed estimates of open tasks
+    pub estimated_secs: u64,
+    /// Estimated time on open tasks not yet covered by time spent
+    pub remaining_secs: u64,
+}
+
+impl Stats {
+    pub fn compute(tasks: &[Task], now: DateTime<Local>) -> Self {
+        let tasks: Vec<Task> = tasks.iter().filter(|task| !task.separator).cloned().collect();
+        let today = now.date_naive();
+        let week_start = today - Days::new(today.weekday().num_days_from_monday() as u64);
+        let completed_since = |start: NaiveDate| {
+            tasks
+                .iter()
+                .filter(|task| task.done && task.completed_at.is_some_and(|at| at.date_naive() >= start))
+                .count()
+        };
+        let open_estimates = tasks
+            .iter()
+            .filter(|task| !task.done)
+            .filter_map(|task| task.estimate_mins.map(|mins| (mins as u64 * 60, task.time_spent_secs)));
+        Stats {
+            total: tasks.len(),
+            done: tasks.iter().filter(|task| task.done).count(),
+            overdue: tasks.iter().filter(|task| is_overdue(task, now)).count(),
+            in_progress: tasks.iter().filter(|task| task.status() == Status::InProgress).count(),
+            completed_today: completed_since(today),
+            completed_this_week: completed_since(week_start),
+            estimated_secs: open_estimates.clone().map(|(estimate, _)| estimate).sum(),
+            remaining_secs: open_estimates.map(|(estimate, spent)| estimate.saturating_sub(spent)).sum(),
+        }
+    }
+
+    /// The summary as display lines, shared by `gottodo stats` and the overlay.
+    pub fn lines(&self) -> Vec<String> {
+        let percent = (self.done * 100).checked_div(self.total).unwrap_or(0);
+        vec![
+            format!("Total:               {}", self.total),
+            format!("Done:                {} ({}%)", self.done, percent),
+            format!("Pending:             {}", self.total - self.done),
+            format!("In progress:         {}", self.in_progress),
+            format!("Overdue:             {}", self.overdue),
+            format!("Completed today:     {}", self.completed_today),
+            format!("Completed this week: {}", self.completed_this_week),
+            format!("Estimated (open):    {}", format_duration(self.estimated_secs)),
+            format!("Remaining effort:    {}", format_duration(self.remaining_secs)),
+        ]
+    }
+}
+
+/// Every category name known from `configured` plus any used by a task,
+/// sorted and without duplicates.
+pub fn category_names<'a>(configured: impl Iterator<Item = &'a String>, tasks: &[Task]) -> Vec<String> {
+    let mut names: Vec<String> = configured.cloned().chain(tasks.iter().filter_map(|task| task.category.clone())).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// The category after `current` in `names`, wrapping through "none" at the end.
+pub fn next_category(current: Option<&str>, names: &[String]) -> Option<String> {
+    match current {
+        None => names.first().cloned(),
+        Some(current) => names.iter().skip_while(|name| name.as_str() != current).nth(1).cloned(),
+    }
+}
+
+/// Whether an open task's due time, or the end of its due day, has passed.
+pub fn is_overdue(task: &Task, now: DateTime<Local>) -> bool {
+    !task.done && task.due_at().is_some_and(|due| due < now.naive_local())
+}
+
+/// The colour of a due moment by how soon it is: red once it has passed,
+/// orange later today, yellow within two days, otherwise none.
+pub fn urgency_color(due: NaiveDateTime, now: NaiveDateTime) -> Option<Color> {
+    match (due.date() - now.date()).num_days() {
+        _ if due < now => Some(Color::Red),
+        0 => Some(Color::Indexed(208)),
+        1 | 2 => Some(Color::Yellow),
+        _ => None,
+    }
+}
+
+/// The first unfinished task `task` waits on, if any. Ids of tasks that no
+/// longer exist don't block. Only direct blockers count, so a cycle in the
+/// file can't loop.
+pub fn open_blocker<'a>(tasks: &'a [Task], task: &Task) -> Option<&'a Task> {
+    task.blocked_by
+        .iter()
+        .find_map(|id| tasks.iter().find(|other| other.id == *id && !other.done))
+}
+
+/// Whether making `blocked` wait on `blocker` would close a loop, i.e.
+/// `blocker` already waits on `blocked`, directly or through other tasks.
+pub fn blocking_cycle(tasks: &[Task], blocked: u64, blocker: u64) -> bool {
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![blocker];
+    while let Some(id) = pending.pop() {
+        if id == blocked {
+            return true;
+        }
+        if seen.insert(id) && let Some(task) = tasks.iter().find(|task| task.id == id) {
+            pending.extend(&task.blocked_by);
+        }
+    }
+    false
+}
+
+pub fn is_snoozed(task: &Task, now: DateTime<Local>) -> bool {
+    task.snoozed_until.is_some_and(|until| until > now)
+}
+
+/// The weeks of the month containing `day`, Monday first; cells outside the
+/// month are `None`.
+pub fn month_grid(day: NaiveDate) -> Vec<[Option<NaiveDate>; 7]> {
+    let first = day.with_day(1).unwrap_or(day);
+    let mut weeks = Vec::new();
+    let mut week = [None; 7];
+    let mut date = first;
+    while date.month() == first.month() {
+        let column = date.weekday().num_days_from_monday() as usize;
+        week[column] = Some(date);
+        if column == 6 {
+            weeks.push(week);
+            week = [None; 7];
+        }
+        date = date + Days::new(1);
+    }
+    if week.iter().any(Option::is_some) {
+        weeks.push(week);
+    }
+    weeks
+}
+
+/// Which agenda heading a due moment (see `Task::due_at`) falls under. "This
+/// week" runs through the coming Sunday.
+pub fn agenda_bucket(due: Option<NaiveDateTime>, now: NaiveDateTime) -> AgendaBucket {
+    let Some(due) = due else {
+        return AgendaBucket::NoDate;
+    };
+    let today = now.date();
+    let days_left_in_week = 6 - today.weekday().num_days_from_monday() as i64;
+    match (due.date() - today).num_days() {
+        _ if due < now => AgendaBucket::Overdue,
+        0 => AgendaBucket::Today,
+        d if d <= days_left_in_week => AgendaBucket::ThisWeek,
+        _ => AgendaBucket::Later,
+    }
+}
+
+/// Lays out the matching tasks as list rows: as-is, or grouped under agenda
+/// headings (ordered by due date within each heading) when `agenda` is set.
+pub fn list_rows(matches: Vec<(usize, Vec<usize>)>, tasks: &[Task], agenda: bool, now: NaiveDateTime) -> Vec<Row> {
+    if !agenda {
+        return matches.into_iter().map(|(i, positions)| Row::Task(i, positions)).collect();
+    }
+    let mut grouped: Vec<(AgendaBucket, usize, Vec<usize>)> = matches
+        .into_iter()
+        .map(|(i, positions)| (agenda_bucket(tasks[i].due_at(), now), i, positions))
+        .collect();
+    grouped.sort_by_key(|(bucket, i, _)| (*bucket, tasks[*i].due_at()));
+    let mut rows = Vec::new();
+    let mut current = None;
+    for (bucket, i, positions) in grouped {
+        if current != Some(bucket) {
+            rows.push(Row::Header(bucket.title().to_string()));
+            current = Some(bucket);
+        }
+        rows.push(Row::Task(i, positions));
+    }
+    rows
+}
+
+/// Lays out the matching tasks under one heading per `#tag`, sorted by name
+/// with untagged tasks last. A task with several tags is listed under each.
+/// Tasks keep their order within a group and are left out when the group's
+/// name is in `collapsed`, so navigating the rows skips them.
+pub fn group_rows(matches: Vec<(usize, Vec<usize>)>, tasks: &[Task], collapsed: &BTreeSet<String>) -> Vec<Row> {
+    // Keyed so that `(true, _)`, the untagged group, sorts last
+    let mut groups: BTreeMap<(bool, String), Vec<Row>> = BTreeMap::new();
+    for (i, positions) in matches {
+        let tags = task_tags(&tasks[i].text);
+        if tags.is_empty() {
+            groups.entry((true, UNTAGGED.to_string())).or_default().push(Row::Task(i, positions));
+            continue;
+        }
+        for tag in tags {
+            groups.entry((false, tag)).or_default().push(Row::Task(i, positions.clone()));
+        }
+    }
+    let mut rows = Vec::new();
+    for ((_, name), members) in groups {
+        let is_collapsed = collapsed.contains(&name);
+        rows.push(Row::Group(name, members.len(), is_collapsed));
+        if !is_collapsed {
+            rows.extend(members);
+        }
+    }
+    rows
+}
+
+/// The heading of the group the row at `pos` is listed under, in `group_rows`
+/// output.
+pub fn group_of(rows: &[Row], pos: usize) -> Option<&str> {
+    rows[..=pos.min(rows.len().saturating_sub(1))].iter().rev().find_map(|row| match row {
+        Row::Group(name, ..) => Some(name.as_str()),
+        _ => None,
+    })
+}
+
+/// The row of task `selected` under the heading `group`, or its first row
+/// when that heading doesn't list it; a task with several tags has a row
+/// under each.
+pub fn task_row(rows: &[Row], selected: usize, group: Option<&str>) -> Option<usize> {
+    let mut heading = None;
+    let mut first = None;
+    for (pos, row) in rows.iter().enumerate() {
+        match row {
+            Row::Group(name, ..) => heading = Some(name.as_str()),
+            Row::Task(i, _) if *i == selected => {
+                if group.is_some() && heading == group {
+                    return Some(pos);
+                }
+                first = first.or(Some(pos));
+            }
+            _ => {}
+        }
+    }
+    first
+}
+
+/// The row the list numbers `number`, counting task rows only, as its
+/// position in `rows` and its task index.
+pub fn numbered_row(rows: &[Row], number: usize) -> Option<(usize, usize)> {
+    let mut task_rows = rows.iter().enumerate().filter_map(|(pos, row)| match row {
+        Row::Task(i, _) => Some((pos, *i)),
+        Row::Header(_) | Row::Group(..) => None,
+    });
+    task_rows.nth(number.checked_sub(1)?)
+}
+
+/// Rebuilds `tasks` from the given old indices, dropping any index not listed.
+/// Returns, for each old index, where that task ended up.
+pub fn apply_order(tasks: &mut Vec<Task>, order: &[usize]) -> Vec<Option<usize>> {
+    let mut remap = vec![None; tasks.len()];
+    for (new, &old) in order.iter().enumerate() {
+        remap[old] = Some(new);
+    }
+    *tasks = order.iter().map(|&i| tasks[i].clone()).collect();
+    remap
+}
+
+/// Where the selection goes after `apply_order`: the same task if it survived,
+/// otherwise the next surviving task after it, otherwise the last one.
+pub fn remap_selection(remap: &[Option<usize>], selected: usize) -> usize {
+    remap
+        .get(selected)
+        .copied()
+        .flatten()
+        .or_else(|| remap.iter().skip(selected).find_map(|i| *i))
+        .or_else(|| remap.iter().rev().find_map(|i| *i))
+        .unwrap_or(0)
+}
+
+pub fn export_markdown(tasks: &[Task], path: &str) -> std::io::Result<()> {
+    let lines: String = tasks
+        .iter()
+        .map(|task| match task.separator {
+            true if task.text.is_empty() => "---\n".to_string(),
+            true => format!("## {}\n", task.text),
+            false => format!("- [{}] {}\n", if task.done { "x" } else { " " }, task.text),
+        })
+        .collect();
+    std::fs::write(path, lines)
+}
+
+/// How many tasks were completed on each of the `days` days up to and
+/// including `today`, oldest first. Days without completions count as zero.
+pub fn completions_per_day(tasks: &[Task], today: NaiveDate, days: usize) -> Vec<u64> {
+    let mut counts = vec![0; days];
+    for task in tasks.iter().filter(|task| task.done) {
+        let Some(day) = task.completed_at.map(|at| at.date_naive()) else { continue };
+        let age = (today - day).num_days();
+        if (0..days as i64).contains(&age) {
+            counts[days - 1 - age as usize] += 1;
+        }
+    }
+    counts
+}
+
+/// Lists the tasks completed on `date` as `- text` lines under a date
+/// heading, in the order they were finished. Tasks without a completion
+/// time are left out.
+pub fn journal(tasks: &[Task], date: NaiveDate) -> String {
+    let mut finished: Vec<(DateTime<Local>, &str)> = tasks
+        .iter()
+        .filter(|task| task.done)
+        .filter_map(|task| task.completed_at.map(|at| (at, task.text.as_str())))
+        .filter(|(at, _)| at.date_naive() == date)
+        .collect();
+    finished.sort_by_key(|(at, _)| *at);
+    let mut lines = format!("## {}\n", date.format("%Y-%m-%d (%A)"));
+    for (_, text) in finished {
+        lines.push_str(&format!("- {}\n", text));
+    }
+    lines
+}
+
+/// A plain HTML page of the list for `--serve`: separators become headings
+/// and done tasks are struck through.
+pub fn tasks_html(tasks: &[Task]) -> String {
+    let pending = tasks.iter().filter(|task| !task.done && !task.separator).count();
+    let mut html = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>gottodo</title></head><body>\n");
+    html.push_str(&format!("<h1>TODO — {} pending</h1>\n<ul>\n", pending));
+    for task in tasks {
+        let text = html_escape(&task.text);
+        let line = if task.separator {
+            format!("</ul>\n<h2>{}</h2>\n<ul>", text)
+        } else if task.done {
+            format!("<li><del>{}</del></li>", text)
+        } else {
+            match task.due_label(DEFAULT_DATE_FORMAT) {
+                Some(due) => format!("<li>{} <small>📅 {}</small></li>", text, due),
+                None => format!("<li>{}</li>", text),
+            }
+        };
+        html.push_str(&line);
+        html.push('\n');
+    }
+    html.push_str("</ul>\n</body></html>\n");
+    html
+}
+
+/// Escapes the characters HTML gives meaning to.
+pub fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders the tasks that have a due date as an iCalendar file with one
+/// VTODO each, stamped with `now`.
+pub fn export_ics(tasks: &[Task], now: DateTime<Local>) -> String {
+    let stamp = |at: DateTime<Local>| at.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec!["BEGIN:VCALENDAR".to_string(), "VERSION:2.0".to_string(), "PRODID:-//gottodo//EN".to_string()];
+    for task in tasks.iter().filter(|task| !task.separator) {
+        let Some(due) = task.due else { continue };
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:task-{}@gottodo", task.id));
+        lines.push(format!("DTSTAMP:{}", stamp(now)));
+        lines.push(format!("SUMMARY:{}", ics_escape(&task.text)));
+        lines.push(match task.due_time {
+            // Floating local time, like the due date itself
+            Some(time) => format!("DUE:{}", due.and_time(time).format("%Y%m%dT%H%M%S")),
+            None => format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")),
+        });
+        lines.push(format!("STATUS:{}", if task.done { "COMPLETED" } else { "NEEDS-ACTION" }));
+        if let Some(completed_at) = task.completed_at.filter(|_| task.done) {
+            lines.push(format!("COMPLETED:{}", stamp(completed_at)));
+        }
+        if let Some(category) = &task.category {
+            lines.push(format!("CATEGORIES:{}", ics_escape(category)));
+        }
+        lines.push("END:VTODO".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_ics_line(line) + "\r\n").collect()
+}
+
+/// Escapes text for an iCalendar TEXT value: backslashes, `;`, `,` and
+/// newlines.
+pub fn ics_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Folds an iCalendar content line so no physical line exceeds 75 octets,
+/// continuing with CRLF and a space and never splitting a UTF-8 character.
+pub fn fold_ics_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    // Continuation lines spend one octet on the leading space
+    let mut room = 75;
+    for c in line.chars() {
+        if c.len_utf8() > room {
+            folded.push_str("\r\n ");
+            room = 74;
+        }
+        folded.push(c);
+        room -= c.len_utf8();
+    }
+    folded
+}
+
+/// Formats a duration as the largest two units, e.g. `1h 05m`, `4m 12s` or `9s`.
+pub fn format_duration(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Looks up a reset period by its `reset_period` name.
+pub fn parse_reset_period(name: &str) -> Option<ResetPeriod> {
+    match name {
+        "daily" => Some(ResetPeriod::Daily),
+        "weekly" => Some(ResetPeriod::Weekly),
+        _ => None,
+    }
+}
+
+/// Whether a list last reset on `last_reset` is due another reset on `today`:
+/// daily lists on any later day, weekly ones once a new Monday-based week
+/// has begun.
+pub fn reset_due(last_reset: NaiveDate, today: NaiveDate, period: ResetPeriod) -> bool {
+    let week_start = |day: NaiveDate| day - Days::new(day.weekday().num_days_from_monday() as u64);
+    match period {
+        ResetPeriod::Daily => today > last_reset,
+        ResetPeriod::Weekly => week_start(today) > week_start(last_reset),
+    }
+}
+
+/// Whether a task was completed more than `days` days before `now`. Tasks
+/// without a completion time (including ones done before it was tracked)
+/// are always kept.
+pub fn should_purge(task: &Task, now: DateTime<Local>, days: u32) -> bool {
+    task.done
+        && task
+            .completed_at
+            .is_some_and(|completed_at| now - completed_at > chrono::Duration::days(days.into()))
+}
+
+/// Describes how long ago `then` was relative to `now`, e.g. `5m ago` or `3d ago`.
+pub fn relative_time(then: DateTime<Local>, now: DateTime<Local>) -> String {
+    let secs = (now - then).num_seconds();
+    match secs {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86_400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86_400),
+    }
+}
+
+/// Ranks tasks against a fuzzy query, best match first (ties keep list order).
+/// Each entry is a task index plus the char positions that matched; an empty
+/// query keeps every task in its stored order.
+pub fn fuzzy_rank(tasks: &[Task], query: &str) -> Vec<(usize, Vec<usize>)> {
+    if query.is_empty() {
+        return (0..tasks.len()).map(|i| (i, Vec::new())).collect();
+    }
+    let matcher = SkimMatcherV2::default();
+    let mut ranked: Vec<(i64, usize, Vec<usize>)> = tasks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, task)| {
+            let (score, positions) = matcher.fuzzy_indices(&task.text, query)?;
+            Some((score, i, positions))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    ranked.into_iter().map(|(_, i, positions)| (i, positions)).collect()
+}
+
+/// The best-ranked task of `ranked` (in `fuzzy_rank` order) that `listed`
+/// shows, so a search lands on a match the current view hasn't filtered out
+/// or folded away.
+pub fn best_listed(ranked: &[usize], listed: &[usize]) -> Option<usize> {
+    ranked.iter().copied().find(|i| listed.contains(i))
+}
+
+/// The first visible task whose text starts with `prefix`, ignoring case,
+/// searching on from `selected` and wrapping around. With `include_selected`
+/// the selected task may match itself; without, it is checked last, so
+/// pressing one letter repeatedly cycles through its matches. Separators
+/// never match.
+pub fn typeahead_target(tasks: &[Task], visible: &[usize], selected: usize, prefix: &str, include_selected: bool) -> Option<usize> {
+    let prefix = prefix.to_lowercase();
+    let start = visible.iter().position(|&i| i == selected).map_or(0, |pos| pos + usize::from(!include_selected));
+    (0..visible.len())
+        .map(|offset| visible[(start + offset) % visible.len()])
+        .find(|&i| !tasks[i].separator && tasks[i].text.to_lowercase().starts_with(&prefix))
+}
+
+/// Moves the selection one step through the visible task indices, stopping
+/// at the ends or, with `wrap`, going round to the other end. A selection
+/// that isn't visible snaps to the first visible task.
+pub fn step_selection(visible: &[usize], selected: usize, forward: bool, wrap: bool) -> usize {
+    match visible.iter().position(|&i| i == selected) {
+        Some(pos) => visible[step_position(pos, visible.len(), forward, wrap)],
+        None => visible.first().copied().unwrap_or(selected),
+    }
+}
+
+/// The position one step from `pos` in a list of `len` rows, clamped to the
+/// ends unless `wrap` is set.
+pub fn step_position(pos: usize, len: usize, forward: bool, wrap: bool) -> usize {
+    match (forward, wrap) {
+        (true, true) => (pos + 1) % len,
+        (true, false) => (pos + 1).min(len - 1),
+        (false, true) => pos.checked_sub(1).unwrap_or(len - 1),
+        (false, false) => pos.saturating_sub(1),
+    }
+}
+
+/// The task shown at 1-based row `number` of the visible list, clamped to
+/// the list's ends.
+pub fn jump_target(visible: &[usize], number: usize) -> Option<usize> {
+    let last = visible.len().checked_sub(1)?;
+    Some(visible[number.saturating_sub(1).min(last)])
+}
+
+/// Keeps the selection on a visible task: the first visible one at or after
+/// `selected`, else the last visible one, else 0 when nothing is visible.
+/// With no filter this is a clamp to `tasks.len() - 1`.
+pub fn clamp_selection(selected: usize, visible: &[usize]) -> usize {
+    if visible.contains(&selected) {
+        return selected;
+    }
+    visible
+        .iter()
+        .find(|&&i| i >= selected)
+        .or(visible.last())
+        .copied()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> DateTime<Local> {
+        let naive = NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap();
+        naive.and_local_timezone(Local).earliest().unwrap()
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    fn texts(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|task| task.text.as_str()).collect()
+    }
+
+    /// A fresh directory under the system temp dir, unique to the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gottodo-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn store_with(items: &[&str]) -> MemoryStore {
+        let mut tasks = Vec::new();
+        let mut last_id = 0;
+        for text in items {
+            add_task(&mut tasks, &mut last_id, text);
+        }
+        MemoryStore { tasks, last_id }
+    }
+
+    #[test]
+    fn add_task_saves_and_loads_back() {
+        let mut store = MemoryStore::default();
+        let mut tasks = store.load().tasks;
+        let mut last_id = store.last_id();
+        assert_eq!(add_task(&mut tasks, &mut last_id, "buy milk"), 1);
+        assert_eq!(add_task(&mut tasks, &mut last_id, "--- Work ---"), 2);
+        store.save(&tasks, last_id).unwrap();
+
+        let loaded = store.load();
+        assert!(loaded.warning.is_none());
+        assert_eq!(texts(&loaded.tasks), ["buy milk", "Work"]);
+        assert!(!loaded.tasks[0].separator);
+        assert!(loaded.tasks[1].separator);
+    }
+
+    #[test]
+    fn add_task_ids_follow_the_highest() {
+        let store = store_with(&["a", "b"]);
+        let (mut tasks, mut last_id) = (store.load().tasks, store.last_id());
+        delete_task(&mut tasks, 0);
+        assert_eq!(add_task(&mut tasks, &mut last_id, "c"), 3);
+    }
+
+    #[test]
+    fn add_task_never_reuses_a_deleted_tasks_id() {
+        let store = store_with(&["a", "b"]);
+        let (mut tasks, mut last_id) = (store.load().tasks, store.last_id());
+        delete_task(&mut tasks, 1);
+        assert_eq!(add_task(&mut tasks, &mut last_id, "c"), 3);
+
+        // Nor once the list has been saved and loaded again
+        let dir = temp_dir("last-id");
+        let mut store = FileStore::new(dir.join("todos.json"));
+        delete_task(&mut tasks, 1);
+        store.save(&tasks, last_id).unwrap();
+        let (mut tasks, mut last_id) = (store.load().tasks, store.last_id());
+        assert_eq!(texts(&tasks), ["a"]);
+        assert_eq!(add_task(&mut tasks, &mut last_id, "d"), 4);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn toggle_done_stamps_and_clears_completion() {
+        let mut store = store_with(&["write report"]);
+        let mut tasks = store.load().tasks;
+        let now = at("2024-03-05", "10:00");
+        tasks[0].in_progress = true;
+        tasks[0].toggle_done(now);
+        store.save(&tasks, 0).unwrap();
+        let task = &store.load().tasks[0];
+        assert!(task.done && !task.in_progress);
+        assert_eq!(task.completed_at, Some(now));
+
+        tasks[0].toggle_done(now);
+        assert!(!tasks[0].done);
+        assert_eq!(tasks[0].completed_at, None);
+    }
+
+    #[test]
+    fn delete_task_remaps_the_rest() {
+        let mut store = store_with(&["a", "b", "c"]);
+        let mut tasks = store.load().tasks;
+        let remap = delete_task(&mut tasks, 1);
+        assert_eq!(remap, [Some(0), None, Some(1)]);
+        assert_eq!(remap_selection(&remap, 1), 1);
+        store.save(&tasks, 0).unwrap();
+        assert_eq!(texts(&store.load().tasks), ["a", "c"]);
+    }
+
+    #[test]
+    fn delete_last_task_selects_the_new_last() {
+        let mut tasks = store_with(&["a", "b"]).load().tasks;
+        let remap = delete_task(&mut tasks, 1);
+        assert_eq!(remap_selection(&remap, 1), 0);
+        let remap = delete_task(&mut tasks, 0);
+        assert!(tasks.is_empty());
+        assert_eq!(remap_selection(&remap, 0), 0);
+    }
+
+    #[test]
+    fn move_to_end_reorders() {
+        let mut store = store_with(&["a", "b", "c"]);
+        let mut tasks = store.load().tasks;
+        let remap = move_to_end(&mut tasks, 2, true);
+        assert_eq!(texts(&tasks), ["c", "a", "b"]);
+        assert_eq!(remap_selection(&remap, 2), 0);
+        let remap = move_to_end(&mut tasks, 0, false);
+        assert_eq!(texts(&tasks), ["a", "b", "c"]);
+        assert_eq!(remap_selection(&remap, 0), 2);
+        store.save(&tasks, 0).unwrap();
+        assert_eq!(texts(&store.load().tasks), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn move_all_to_end_keeps_relative_order() {
+        let mut tasks = store_with(&["a", "b", "c", "d"]).load().tasks;
+        let ids: HashSet<u64> = [2, 4].into();
+        move_all_to_end(&mut tasks, &ids, true);
+        assert_eq!(texts(&tasks), ["b", "d", "a", "c"]);
+        move_all_to_end(&mut tasks, &ids, false);
+        assert_eq!(texts(&tasks), ["a", "c", "b", "d"]);
+    }
+
+    #[test]
+    fn sorted_order_by_each_key() {
+        let mut tasks = store_with(&["beta", "Alpha", "gamma", "delta"]).load().tasks;
+        tasks[0].done = true;
+        tasks[2].due = Some(day("2024-03-07"));
+        tasks[3].due = Some(day("2024-03-07"));
+        tasks[3].due_time = NaiveTime::from_hms_opt(9, 0, 0);
+        tasks[1].due = Some(day("2024-03-09"));
+
+        assert_eq!(sorted_order(&tasks, SortKey::Alpha), [1, 0, 3, 2]);
+        assert_eq!(sorted_order(&tasks, SortKey::DoneLast), [1, 2, 3, 0]);
+        // A timed task comes before a date-only one on the same day
+        assert_eq!(sorted_order(&tasks, SortKey::Due), [3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn sorted_order_applies_through_the_store() {
+        let mut store = store_with(&["c", "a", "b"]);
+        let mut tasks = store.load().tasks;
+        let order = sorted_order(&tasks, SortKey::Alpha);
+        apply_order(&mut tasks, &order);
+        store.save(&tasks, 0).unwrap();
+        assert_eq!(texts(&store.load().tasks), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn file_store_save_replaces_the_file_atomically() {
+        let dir = temp_dir("atomic-save");
+        let path = dir.join("todos.json");
+        std::fs::write(&path, "[]").unwrap();
+        let mut store = FileStore::new(&path);
+        let tasks = store_with(&["a", "b"]).load().tasks;
+        store.save(&tasks, 0).unwrap();
+
+        assert!(!dir.join("todos.json.tmp").exists());
+        let loaded = store.load();
+        assert!(loaded.warning.is_none());
+        assert_eq!(texts(&loaded.tasks), ["a", "b"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn file_store_save_failure_keeps_the_old_file() {
+        let dir = temp_dir("save-failure");
+        let path = dir.join("todos.json");
+        std::fs::write(&path, "[]").unwrap();
+        // A directory where the temp file should go makes the write fail
+        std::fs::create_dir(dir.join("todos.json.tmp")).unwrap();
+        let tasks = store_with(&["a"]).load().tasks;
+        assert!(FileStore::new(&path).save(&tasks, 1).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn file_store_read_leaves_a_missing_file_alone() {
+        let dir = temp_dir("read-missing");
+        let path = dir.join("todos.json");
+        let loaded = FileStore::new(&path).read();
+        assert!(loaded.tasks.is_empty() && loaded.warning.is_none());
+        assert!(!path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn file_store_read_recovers_without_a_backup() {
+        let dir = temp_dir("read-corrupt");
+        let path = dir.join("todos.json");
+        let tasks = store_with(&["a"]).load().tasks;
+        let json = serde_json::to_string(&tasks).unwrap();
+        std::fs::write(&path, &json[..json.len() - 1]).unwrap();
+        let loaded = FileStore::new(&path).read();
+        assert_eq!(texts(&loaded.tasks), ["a"]);
+        assert!(loaded.warning.unwrap().contains("corrupt"));
+        assert!(!dir.join("todos.json.corrupt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn fuzzy_rank_puts_the_best_match_first() {
+        let tasks = store_with(&["read mail", "email", "call mom", "mail"]).load().tasks;
+        let ranked: Vec<usize> = fuzzy_rank(&tasks, "mail").into_iter().map(|(i, _)| i).collect();
+        assert_eq!(ranked.first(), Some(&3));
+        assert!(!ranked.contains(&2));
+        assert_eq!(ranked.len(), 3);
+    }
+
+    #[test]
+    fn fuzzy_rank_ties_keep_list_order() {
+        let tasks = store_with(&["fix bug", "fix bug", "fix bug"]).load().tasks;
+        let ranked: Vec<usize> = fuzzy_rank(&tasks, "bug").into_iter().map(|(i, _)| i).collect();
+        assert_eq!(ranked, [0, 1, 2]);
+    }
+
+    #[test]
+    fn fuzzy_rank_reports_matched_positions() {
+        let tasks = store_with(&["a task"]).load().tasks;
+        assert_eq!(fuzzy_rank(&tasks, "tsk"), [(0, vec![2, 4, 5])]);
+        assert!(fuzzy_rank(&tasks, "zzz").is_empty());
+    }
+
+    #[test]
+    fn fuzzy_rank_empty_query_keeps_everything_in_order() {
+        let tasks = store_with(&["b", "a"]).load().tasks;
+        assert_eq!(fuzzy_rank(&tasks, ""), [(0, vec![]), (1, vec![])]);
+    }
+
+    #[test]
+    fn best_listed_skips_matches_the_view_hides() {
+        assert_eq!(best_listed(&[3, 0, 5], &[0, 1, 5]), Some(0));
+        assert_eq!(best_listed(&[3], &[0, 1]), None);
+    }
+
+    #[test]
+    fn initial_sort_uses_the_config_default_for_each_mode() {
+        for (name, key) in [("none", None), ("alpha", Some(SortKey::Alpha)), ("done-last", Some(SortKey::DoneLast)), ("due", Some(SortKey::Due))] {
+            assert_eq!(initial_sort(None, name), key, "default_sort = {}", name);
+        }
+        assert_eq!(initial_sort(None, "priority"), None);
+    }
+
+    #[test]
+    fn initial_sort_prefers_the_saved_sort_for_each_mode() {
+        for key in [SortKey::Alpha, SortKey::DoneLast, SortKey::Due] {
+            assert_eq!(initial_sort(Some(key.name()), "none"), Some(key));
+            assert_eq!(initial_sort(Some(key.name()), "alpha"), Some(key));
+        }
+        // Own order picked in the app beats a configured sort
+        assert_eq!(initial_sort(Some("none"), "due"), None);
+        // A name from a newer or hand-edited prefs.json falls back to the config
+        assert_eq!(initial_sort(Some("priority"), "due"), Some(SortKey::Due));
+    }
+
+    #[test]
+    fn each_sort_mode_leaves_the_stored_order_alone() {
+        let mut tasks = store_with(&["b", "c", "a"]).load().tasks;
+        tasks[0].done = true;
+        tasks[1].due = Some(day("2024-01-01"));
+        let before = tasks.clone();
+        for (name, expected) in [("none", vec![0, 1, 2]), ("alpha", vec![2, 0, 1]), ("done-last", vec![1, 2, 0]), ("due", vec![1, 0, 2])] {
+            let order = initial_sort(None, name).map_or_else(|| (0..tasks.len()).collect(), |key| sorted_order(&tasks, key));
+            assert_eq!(order, expected, "sort {}", name);
+        }
+        assert_eq!(tasks, before);
+    }
+
+    fn task_rows(rows: &[Row]) -> Vec<String> {
+        rows.iter()
+            .map(|row| match row {
+                Row::Header(title) => format!("= {}", title),
+                Row::Group(name, count, collapsed) => format!("{} {} ({})", if *collapsed { "+" } else { "-" }, name, count),
+                Row::Task(i, _) => i.to_string(),
+            })
+            .collect()
+    }
+
+    fn all_matches(tasks: &[Task]) -> Vec<(usize, Vec<usize>)> {
+        (0..tasks.len()).map(|i| (i, Vec::new())).collect()
+    }
+
+    #[test]
+    fn task_tags_reads_one_task() {
+        assert_eq!(task_tags("call #Bob, then #work #work."), ["#Bob", "#work"]);
+        assert!(task_tags("issue # 5 and a#b").is_empty());
+        let tasks = store_with(&["a #x", "b #y #x"]).load().tasks;
+        assert_eq!(all_tags(&tasks), ["#x", "#y"]);
+    }
+
+    #[test]
+    fn group_rows_lists_tasks_under_each_tag() {
+        let tasks = store_with(&["plan #work", "gym #home #work", "nap", "shop #home"]).load().tasks;
+        let rows = group_rows(all_matches(&tasks), &tasks, &BTreeSet::new());
+        assert_eq!(task_rows(&rows), ["- #home (2)", "1", "3", "- #work (2)", "0", "1", "- Untagged (1)", "2"]);
+    }
+
+    #[test]
+    fn group_rows_leaves_out_collapsed_children() {
+        let tasks = store_with(&["plan #work", "gym #home #work", "nap"]).load().tasks;
+        let collapsed: BTreeSet<String> = ["#home".to_string(), UNTAGGED.to_string()].into();
+        let rows = group_rows(all_matches(&tasks), &tasks, &collapsed);
+        assert_eq!(task_rows(&rows), ["+ #home (1)", "- #work (2)", "0", "1", "+ Untagged (1)"]);
+        // Stepping down from the last task row lands on the collapsed heading, not a hidden task
+        let last = task_row(&rows, 1, Some("#work")).unwrap();
+        assert!(matches!(rows[step_position(last, rows.len(), true, false)], Row::Group(ref name, ..) if name == UNTAGGED));
+    }
+
+    #[test]
+    fn numbered_row_counts_task_rows_as_grouped() {
+        let tasks = store_with(&["plan #work", "gym #home", "nap", "read #home"]).load().tasks;
+        let collapsed: BTreeSet<String> = ["#work".to_string()].into();
+        let rows = group_rows(all_matches(&tasks), &tasks, &collapsed);
+        assert_eq!(task_rows(&rows), ["- #home (2)", "1", "3", "+ #work (1)", "- Untagged (1)", "2"]);
+        assert_eq!(numbered_row(&rows, 1), Some((1, 1)));
+        // The collapsed #work task has no number, so 3 is the untagged one
+        assert_eq!(numbered_row(&rows, 3), Some((5, 2)));
+        assert_eq!(numbered_row(&rows, 4), None);
+        assert_eq!(numbered_row(&rows, 0), None);
+    }
+
+    #[test]
+    fn task_row_finds_the_row_in_the_given_group() {
+        let tasks = store_with(&["gym #home #work", "plan #work"]).load().tasks;
+        let rows = group_rows(all_matches(&tasks), &tasks, &BTreeSet::new());
+        assert_eq!(task_rows(&rows), ["- #home (1)", "0", "- #work (2)", "0", "1"]);
+        assert_eq!(task_row(&rows, 0, Some("#work")), Some(3));
+        assert_eq!(task_row(&rows, 0, Some("#home")), Some(1));
+        // Elsewhere or unknown, the first row is used
+        assert_eq!(task_row(&rows, 0, None), Some(1));
+        assert_eq!(task_row(&rows, 1, Some("#home")), Some(4));
+        assert_eq!(task_row(&rows, 7, None), None);
+        assert_eq!(group_of(&rows, 3), Some("#work"));
+        assert_eq!(group_of(&rows, 1), Some("#home"));
+    }
+
+    fn naive(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn urgency_color_overdue_once_the_moment_passes() {
+        let now = naive("2024-03-05", "12:00:00");
+        assert_eq!(urgency_color(naive("2024-03-05", "11:59:59"), now), Some(Color::Red));
+        assert_eq!(urgency_color(naive("2024-02-01", "23:59:59"), now), Some(Color::Red));
+        // Due this very second isn't overdue yet
+        assert_eq!(urgency_color(now, now), Some(Color::Indexed(208)));
+    }
+
+    #[test]
+    fn urgency_color_today_until_midnight() {
+        let now = naive("2024-03-05", "12:00:00");
+        assert_eq!(urgency_color(naive("2024-03-05", "23:59:59"), now), Some(Color::Indexed(208)));
+        // A date-only task is due at the end of its day
+        let task = Task { due: Some(day("2024-03-05")), ..Task::new(1, "a") };
+        assert_eq!(urgency_color(task.due_at().unwrap(), now), Some(Color::Indexed(208)));
+    }
+
+    #[test]
+    fn urgency_color_soon_for_the_next_two_days() {
+        let now = naive("2024-03-05", "23:59:59");
+        assert_eq!(urgency_color(naive("2024-03-06", "00:00:00"), now), Some(Color::Yellow));
+        assert_eq!(urgency_color(naive("2024-03-07", "23:59:59"), now), Some(Color::Yellow));
+    }
+
+    #[test]
+    fn urgency_color_none_when_far_off() {
+        let now = naive("2024-03-05", "00:00:00");
+        assert_eq!(urgency_color(naive("2024-03-08", "00:00:00"), now), None);
+        assert_eq!(urgency_color(naive("2025-01-01", "09:00:00"), now), None);
+    }
+
+    /// `text` after `parse_markup` as runs of shown text and their emphasis.
+    fn markup_runs(text: &str) -> Vec<(String, Emphasis)> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut runs: Vec<(String, Emphasis)> = Vec::new();
+        for (i, emphasis) in parse_markup(text) {
+            match runs.last_mut() {
+                Some((run, last)) if *last == emphasis => run.push(chars[i]),
+                _ => runs.push((chars[i].to_string(), emphasis)),
+            }
+        }
+        runs
+    }
+
+    fn run(text: &str, emphasis: Emphasis) -> (String, Emphasis) {
+        (text.to_string(), emphasis)
+    }
+
+    #[test]
+    fn parse_markup_styles_each_marker() {
+        use Emphasis::*;
+        assert_eq!(
+            markup_runs("a *b* _c_ `d`"),
+            [run("a ", Plain), run("b", Bold), run(" ", Plain), run("c", Italic), run(" ", Plain), run("d", Code)]
+        );
+        assert_eq!(markup_runs("(*wow*)."), [run("(", Plain), run("wow", Bold), run(").", Plain)]);
+    }
+
+    #[test]
+    fn parse_markup_does_not_nest() {
+        use Emphasis::*;
+        assert_eq!(markup_runs("*bold _not italic_*"), [run("bold _not italic_", Bold)]);
+        assert_eq!(markup_runs("`*raw*`"), [run("*raw*", Code)]);
+        // The first closing marker ends the span
+        assert_eq!(markup_runs("*a* b*"), [run("a", Bold), run(" b*", Plain)]);
+    }
+
+    #[test]
+    fn parse_markup_keeps_unclosed_markers() {
+        use Emphasis::*;
+        assert_eq!(markup_runs("*open"), [run("*open", Plain)]);
+        assert_eq!(markup_runs("a _b"), [run("a _b", Plain)]);
+        assert_eq!(markup_runs("`"), [run("`", Plain)]);
+        assert_eq!(markup_runs("**"), [run("**", Plain)]);
+    }
+
+    #[test]
+    fn parse_markup_leaves_markers_inside_words_alone() {
+        use Emphasis::*;
+        assert_eq!(markup_runs("2*3*4"), [run("2*3*4", Plain)]);
+        assert_eq!(markup_runs("snake_case_name"), [run("snake_case_name", Plain)]);
+        assert_eq!(markup_runs("* spaced *"), [run("* spaced *", Plain)]);
+        // Code spans don't care about word edges
+        assert_eq!(markup_runs("x`y`z"), [run("x", Plain), run("y", Code), run("z", Plain)]);
+    }
+
+    #[test]
+    fn parse_markup_reports_source_positions() {
+        assert_eq!(parse_markup("é *ü*"), [(0, Emphasis::Plain), (1, Emphasis::Plain), (3, Emphasis::Bold)]);
+    }
+
+    #[test]
+    fn export_ics_writes_a_vtodo_per_dated_task() {
+        let now = at("2024-03-05", "10:00");
+        let mut tasks = store_with(&["dentist", "no date", "--- Work ---", "call, then; go"]).load().tasks;
+        tasks[0].due = Some(day("2024-03-07"));
+        tasks[0].due_time = NaiveTime::from_hms_opt(14, 30, 0);
+        tasks[0].category = Some("home".to_string());
+        tasks[2].due = Some(day("2024-03-07"));
+        tasks[3].due = Some(day("2024-03-08"));
+        tasks[3].toggle_done(now);
+        let stamp = now.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ");
+        let expected = [
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            "PRODID:-//gottodo//EN".to_string(),
+            "BEGIN:VTODO".to_string(),
+            "UID:task-1@gottodo".to_string(),
+            format!("DTSTAMP:{}", stamp),
+            "SUMMARY:dentist".to_string(),
+            "DUE:20240307T143000".to_string(),
+            "STATUS:NEEDS-ACTION".to_string(),
+            "CATEGORIES:home".to_string(),
+            "END:VTODO".to_string(),
+            "BEGIN:VTODO".to_string(),
+            "UID:task-4@gottodo".to_string(),
+            format!("DTSTAMP:{}", stamp),
+            "SUMMARY:call\\, then\\; go".to_string(),
+            "DUE;VALUE=DATE:20240308".to_string(),
+            "STATUS:COMPLETED".to_string(),
+            format!("COMPLETED:{}", stamp),
+            "END:VTODO".to_string(),
+            "END:VCALENDAR".to_string(),
+        ];
+        assert_eq!(export_ics(&tasks, now), expected.map(|line| line + "\r\n").concat());
+    }
+
+    #[test]
+    fn ics_escape_handles_special_characters() {
+        assert_eq!(ics_escape("a,b;c\\d"), "a\\,b\\;c\\\\d");
+        assert_eq!(ics_escape("two\r\nlines"), "two\\nlines");
+        assert_eq!(ics_escape("plain"), "plain");
+    }
+
+    #[test]
+    fn fold_ics_line_keeps_short_lines() {
+        let line = "x".repeat(75);
+        assert_eq!(fold_ics_line(&line), line);
+    }
+
+    #[test]
+    fn fold_ics_line_splits_at_75_octets() {
+        let folded = fold_ics_line(&"x".repeat(160));
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(lines.iter().map(|line| line.len()).collect::<Vec<_>>(), [75, 75, 12]);
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+    }
+
+    #[test]
+    fn fold_ics_line_never_splits_a_multibyte_character() {
+        // 73 ASCII octets leave room for 2 more, so the 3-octet € moves down
+        let line = format!("{}€€", "x".repeat(73));
+        let folded = fold_ics_line(&line);
+        assert_eq!(folded, format!("{}\r\n €€", "x".repeat(73)));
+        let folded = fold_ics_line(&"ü".repeat(100));
+        for physical in folded.split("\r\n") {
+            assert!(physical.len() <= 75, "{} octets", physical.len());
+        }
+        assert_eq!(folded.replace("\r\n ", ""), "ü".repeat(100));
+    }
+
+    fn tasks_json(items: &[&str]) -> String {
+        serde_json::to_string_pretty(&store_with(items).load().tasks).unwrap()
+    }
+
+    #[test]
+    fn recover_tasks_reads_a_truncated_array() {
+        let json = tasks_json(&["a", "b"]);
+        // Cut off just before the closing bracket
+        let cut = json.trim_end().trim_end_matches(']');
+        assert_eq!(texts(&recover_tasks(cut)), ["a", "b"]);
+    }
+
+    #[test]
+    fn recover_tasks_drops_a_trailing_partial_object() {
+        let json = tasks_json(&["a", "b", "c"]);
+        let cut = &json[..json.rfind("\"text\": \"c\"").unwrap()];
+        assert_eq!(texts(&recover_tasks(cut)), ["a", "b"]);
+    }
+
+    #[test]
+    fn recover_tasks_stops_at_a_broken_element() {
+        let json = r#"[{"text": "a", "done": false}, {"text": 5}, {"text": "c", "done": false}]"#;
+        assert_eq!(texts(&recover_tasks(json)), ["a"]);
+        assert!(recover_tasks("{\"not\": \"a list\"}").is_empty());
+        assert!(recover_tasks("").is_empty());
+    }
+
+    #[test]
+    fn file_store_load_backs_up_a_corrupt_file() {
+        let dir = temp_dir("load-corrupt");
+        let path = dir.join("todos.json");
+        let json = tasks_json(&["a", "b"]);
+        let cut = &json[..json.len() - 20];
+        std::fs::write(&path, cut).unwrap();
+        let loaded = FileStore::new(&path).load();
+        assert_eq!(texts(&loaded.tasks), ["a"]);
+        let warning = loaded.warning.unwrap();
+        assert!(warning.contains("backed up to") && warning.contains("recovered 1 task(s)"), "{}", warning);
+        assert_eq!(std::fs::read_to_string(dir.join("todos.json.corrupt")).unwrap(), cut);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn file_store_load_creates_a_missing_file() {
+        let dir = temp_dir("load-missing");
+        let path = dir.join("todos.json");
+        let loaded = FileStore::new(&path).load();
+        assert!(loaded.tasks.is_empty() && loaded.warning.is_none());
+        assert!(path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn next_reminder_waits_for_a_future_due_time() {
+        let due = at("2024-03-05", "10:00");
+        assert_eq!(next_reminder(due, 15, at("2024-03-05", "09:00")), due);
+    }
+
+    #[test]
+    fn next_reminder_fires_once_after_missed_intervals() {
+        let due = at("2024-03-05", "10:00");
+        // Exactly on a grid point counts as that one having gone off
+        assert_eq!(next_reminder(due, 15, due), at("2024-03-05", "10:15"));
+        assert_eq!(next_reminder(due, 15, at("2024-03-05", "10:14")), at("2024-03-05", "10:15"));
+        // An hour and a bit late skips the missed ones instead of queueing them
+        assert_eq!(next_reminder(due, 15, at("2024-03-05", "11:07")), at("2024-03-05", "11:15"));
+        assert_eq!(next_reminder(due, 60, at("2024-03-06", "10:00")), at("2024-03-06", "11:00"));
+    }
+
+    #[test]
+    fn next_reminder_treats_a_zero_interval_as_one_minute() {
+        let due = at("2024-03-05", "10:00");
+        assert_eq!(next_reminder(due, 0, at("2024-03-05", "10:05")), at("2024-03-05", "10:06"));
+    }
+
+    #[test]
+    fn no_reminder_interval_is_kept_as_none() {
+        // An empty prompt clears the reminder, and older files have none
+        assert_eq!(parse_minutes("").unwrap(), None);
+        let task: Task = serde_json::from_str(r#"{"text": "a", "done": false}"#).unwrap();
+        assert_eq!(task.reminder_interval_mins, None);
+    }
+
+    /// Tasks 1..=n where task `a` waits on task `b` for each `(a, b)`.
+    fn blocked_tasks(n: usize, links: &[(u64, u64)]) -> Vec<Task> {
+        let mut tasks: Vec<Task> = (1..=n as u64).map(|id| Task::new(id, &format!("task {}", id))).collect();
+        for &(blocked, blocker) in links {
+            tasks[blocked as usize - 1].blocked_by.push(blocker);
+        }
+        tasks
+    }
+
+    #[test]
+    fn blocking_cycle_direct() {
+        let tasks = blocked_tasks(2, &[(2, 1)]);
+        assert!(blocking_cycle(&tasks, 1, 2));
+        assert!(!blocking_cycle(&tasks, 2, 1));
+    }
+
+    #[test]
+    fn blocking_cycle_indirect() {
+        let tasks = blocked_tasks(4, &[(2, 1), (3, 2), (4, 3)]);
+        assert!(blocking_cycle(&tasks, 1, 4));
+        assert!(blocking_cycle(&tasks, 2, 4));
+        assert!(!blocking_cycle(&tasks, 4, 1));
+    }
+
+    #[test]
+    fn blocking_cycle_self_block() {
+        let tasks = blocked_tasks(1, &[]);
+        assert!(blocking_cycle(&tasks, 1, 1));
+    }
+
+    #[test]
+    fn blocking_cycle_survives_a_loop_already_in_the_file() {
+        let tasks = blocked_tasks(3, &[(1, 2), (2, 1)]);
+        assert!(!blocking_cycle(&tasks, 3, 1));
+        assert!(blocking_cycle(&tasks, 1, 2));
+    }
+
+    #[test]
+    fn open_blocker_finds_the_first_open_one() {
+        let mut tasks = blocked_tasks(3, &[(3, 1), (3, 2)]);
+        assert_eq!(open_blocker(&tasks, &tasks[2]).map(|task| task.id), Some(1));
+        tasks[0].toggle_done(at("2024-03-05", "10:00"));
+        assert_eq!(open_blocker(&tasks, &tasks[2]).map(|task| task.id), Some(2));
+        tasks[1].toggle_done(at("2024-03-05", "10:00"));
+        assert!(open_blocker(&tasks, &tasks[2]).is_none());
+    }
+
+    #[test]
+    fn open_blocker_ignores_deleted_blockers() {
+        let mut tasks = blocked_tasks(2, &[(2, 1)]);
+        delete_task(&mut tasks, 0);
+        assert!(open_blocker(&tasks, &tasks[0]).is_none());
+        // Even a cycle left in the file resolves in one step
+        let tasks = blocked_tasks(2, &[(1, 2), (2, 1)]);
+        assert_eq!(open_blocker(&tasks, &tasks[0]).map(|task| task.id), Some(2));
+    }
+
+    fn time(text: &str) -> Option<NaiveTime> {
+        Some(NaiveTime::parse_from_str(text, "%H:%M").unwrap())
+    }
+
+    #[test]
+    fn parse_due_input_relative_words() {
+        let today = day("2024-03-05");
+        assert_eq!(parse_due_input("today", today), Ok(Some((today, None))));
+        assert_eq!(parse_due_input(" Tomorrow ", today), Ok(Some((day("2024-03-06"), None))));
+        assert_eq!(parse_due_input("+3", today), Ok(Some((day("2024-03-08"), None))));
+        assert_eq!(parse_due_input("+3d", today), Ok(Some((day("2024-03-08"), None))));
+        assert_eq!(parse_due_input("+2w", today), Ok(Some((day("2024-03-19"), None))));
+        assert_eq!(parse_due_input("", today), Ok(None));
+    }
+
+    #[test]
+    fn parse_due_input_date_with_time() {
+        let today = day("2024-03-05");
+        assert_eq!(parse_due_input("2024-05-17 14:30", today), Ok(Some((day("2024-05-17"), time("14:30")))));
+        assert_eq!(parse_due_input("tomorrow 09:00", today), Ok(Some((day("2024-03-06"), time("09:00")))));
+        assert_eq!(parse_due_input("+1w  18:45", today), Ok(Some((day("2024-03-12"), time("18:45")))));
+        // A time alone is for today
+        assert_eq!(parse_due_input("14:30", today), Ok(Some((today, time("14:30")))));
+    }
+
+    #[test]
+    fn parse_due_input_rejects_invalid_times() {
+        let today = day("2024-03-05");
+        for input in ["tomorrow 25:00", "today 12:60", "2024-03-05 noon", "24:00", "12:3x"] {
+            assert!(parse_due_input(input, today).is_err(), "{}", input);
+        }
+        assert!(parse_due_input("2024-02-30", today).is_err());
+        assert!(parse_due_input("next week", today).is_err());
+    }
+
+    #[test]
+    fn parse_due_input_accepts_past_dates() {
+        let today = day("2024-03-05");
+        assert_eq!(parse_due_input("2024-03-04", today), Ok(Some((day("2024-03-04"), None))));
+        assert_eq!(parse_due_input("2020-01-01 08:00", today), Ok(Some((day("2020-01-01"), time("08:00")))));
+        // Offsets only count forward
+        assert!(parse_due_input("-1", today).is_err());
+    }
+
+    #[test]
+    fn format_date_for_each_setting() {
+        let date = day("2024-03-05");
+        for (pattern, expected) in [
+            (DEFAULT_DATE_FORMAT, "2024-03-05"),
+            ("%d.%m.%Y", "05.03.2024"),
+            ("%m/%d", "03/05"),
+            ("%e %b", " 5 Mar"),
+            ("%a %d %B", "Tue 05 March"),
+            ("%Y-W%V", "2024-W10"),
+        ] {
+            assert_eq!(format_date(date, pattern).as_deref(), Some(expected), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn format_date_rejects_patterns_a_date_cannot_fill() {
+        let date = day("2024-03-05");
+        assert_eq!(format_date(date, "%H:%M"), None);
+        assert_eq!(format_date(date, "%Q"), None);
+    }
+
+    #[test]
+    fn due_label_uses_the_date_format() {
+        let mut task = Task { due: Some(day("2024-03-05")), ..Task::new(1, "a") };
+        assert_eq!(task.due_label("%d.%m.%Y").as_deref(), Some("05.03.2024"));
+        task.due_time = NaiveTime::from_hms_opt(9, 5, 0);
+        assert_eq!(task.due_label("%m/%d").as_deref(), Some("03/05 09:05"));
+        // A broken format still shows the date
+        assert_eq!(task.due_label("%H").as_deref(), Some("2024-03-05 09:05"));
+    }
+
+    #[test]
+    fn relative_time_unit_boundaries() {
+        let now = at("2024-03-05", "12:00");
+        let ago = |secs: i64| relative_time(now - chrono::Duration::seconds(secs), now);
+        assert_eq!(ago(0), "just now");
+        assert_eq!(ago(59), "just now");
+        assert_eq!(ago(60), "1m ago");
+        assert_eq!(ago(3599), "59m ago");
+        assert_eq!(ago(3600), "1h ago");
+        assert_eq!(ago(86_399), "23h ago");
+        assert_eq!(ago(86_400), "1d ago");
+        assert_eq!(ago(400 * 86_400), "400d ago");
+    }
+
+    #[test]
+    fn relative_time_in_the_future_reads_just_now() {
+        // A clock set back since the task was made mustn't give "-5m ago"
+        let now = at("2024-03-05", "12:00");
+        assert_eq!(relative_time(now + chrono::Duration::minutes(5), now), "just now");
+        assert_eq!(relative_time(now + chrono::Duration::days(3), now), "just now");
+    }
+
+    fn replacement(find: &str, replace: &str, ignore_case: bool) -> Replacement {
+        Replacement { find: find.to_string(), replace: replace.to_string(), ignore_case }
+    }
+
+    #[test]
+    fn parse_substitution_reads_find_replace_and_flags() {
+        assert_eq!(parse_substitution("foo/bar"), Ok(replacement("foo", "bar", false)));
+        assert_eq!(parse_substitution("foo/bar/"), Ok(replacement("foo", "bar", false)));
+        assert_eq!(parse_substitution("foo//i"), Ok(replacement("foo", "", true)));
+        assert_eq!(parse_command("s/a/b/i"), Ok(Command::Replace(replacement("a", "b", true))));
+    }
+
+    #[test]
+    fn parse_substitution_escaped_delimiters() {
+        assert_eq!(parse_substitution(r"a\/b/c\/d/"), Ok(replacement("a/b", "c/d", false)));
+        // A backslash before anything else is kept
+        assert_eq!(parse_substitution(r"a\b/c"), Ok(replacement(r"a\b", "c", false)));
+    }
+
+    #[test]
+    fn parse_substitution_rejects_bad_input() {
+        assert!(parse_substitution("").is_err());
+        assert!(parse_substitution("/x/").is_err());
+        assert!(parse_substitution("foo").is_err());
+        assert!(parse_substitution("a/b/c/d").is_err());
+        assert_eq!(parse_substitution("a/b/g"), Err("Unknown flag 'g' (only i is supported)".to_string()));
+    }
+
+    #[test]
+    fn replace_text_replaces_every_match() {
+        assert_eq!(replace_text("a-a-a", &replacement("a", "bb", false)).as_deref(), Some("bb-bb-bb"));
+        // Replacements aren't searched again
+        assert_eq!(replace_text("aa", &replacement("a", "aa", false)).as_deref(), Some("aaaa"));
+        assert_eq!(replace_text("Foo foo FOO", &replacement("foo", "x", true)).as_deref(), Some("x x x"));
+        assert_eq!(replace_text("Straße STRASSE", &replacement("straße", "st", true)).as_deref(), Some("st STRASSE"));
+    }
+
+    #[test]
+    fn replace_text_without_a_match() {
+        assert_eq!(replace_text("hello", &replacement("bye", "x", false)), None);
+        assert_eq!(replace_text("Hello", &replacement("hello", "x", false)), None);
+    }
+
+    #[test]
+    fn agenda_bucket_date_only_task_due_today_is_today() {
+        // Late in the day, a task due today without a time isn't overdue yet
+        let now = naive("2024-03-05", "23:30:00");
+        let task = Task { due: Some(day("2024-03-05")), ..Task::new(1, "a") };
+        assert_eq!(agenda_bucket(task.due_at(), now), AgendaBucket::Today);
+        let yesterday = Task { due: Some(day("2024-03-04")), ..Task::new(2, "b") };
+        assert_eq!(agenda_bucket(yesterday.due_at(), now), AgendaBucket::Overdue);
+    }
+
+    #[test]
+    fn agenda_bucket_timed_task_is_overdue_once_passed() {
+        let now = naive("2024-03-05", "12:00:00");
+        assert_eq!(agenda_bucket(Some(naive("2024-03-05", "11:59:00")), now), AgendaBucket::Overdue);
+        assert_eq!(agenda_bucket(Some(now), now), AgendaBucket::Today);
+        assert_eq!(agenda_bucket(Some(naive("2024-03-05", "18:00:00")), now), AgendaBucket::Today);
+    }
+
+    #[test]
+    fn agenda_bucket_this_week_runs_through_sunday() {
+        // 2024-03-05 is a Tuesday
+        let now = naive("2024-03-05", "09:00:00");
+        assert_eq!(agenda_bucket(Some(naive("2024-03-06", "00:00:00")), now), AgendaBucket::ThisWeek);
+        assert_eq!(agenda_bucket(Some(naive("2024-03-10", "23:59:59")), now), AgendaBucket::ThisWeek);
+        assert_eq!(agenda_bucket(Some(naive("2024-03-11", "00:00:00")), now), AgendaBucket::Later);
+        // On a Sunday the week ends today
+        let sunday = naive("2024-03-10", "09:00:00");
+        assert_eq!(agenda_bucket(Some(naive("2024-03-11", "09:00:00")), sunday), AgendaBucket::Later);
+        assert_eq!(agenda_bucket(None, now), AgendaBucket::NoDate);
+    }
+
+    #[test]
+    fn list_rows_groups_the_agenda_in_bucket_order() {
+        let now = naive("2024-03-05", "09:00:00");
+        let mut tasks = store_with(&["later", "none", "today", "late"]).load().tasks;
+        tasks[0].due = Some(day("2024-04-01"));
+        tasks[2].due = Some(day("2024-03-05"));
+        tasks[3].due = Some(day("2024-03-01"));
+        let rows = list_rows(all_matches(&tasks), &tasks, true, now);
+        assert_eq!(task_rows(&rows), ["= Overdue", "3", "= Today", "2", "= Later", "0", "= No date", "1"]);
+    }
+
+    #[test]
+    fn parse_command_toggle() {
+        assert_eq!(parse_command("toggle 3"), Ok(Command::Toggle(3)));
+        assert_eq!(parse_command("  done 12 "), Ok(Command::Toggle(12)));
+        assert_eq!(parse_command("toggle 0"), Err("Not a task number: '0'".to_string()));
+        assert_eq!(parse_command("toggle x"), Err("Not a task number: 'x'".to_string()));
+        assert_eq!(parse_command("toggle -1"), Err("Not a task number: '-1'".to_string()));
+        assert_eq!(parse_command("toggle"), Err("Usage: toggle <number>".to_string()));
+        assert_eq!(parse_command("toggle 1 2"), Err("Usage: toggle <number>".to_string()));
+    }
+
+    #[test]
+    fn parse_command_other_commands() {
+        assert_eq!(parse_command("sort alpha"), Ok(Command::Sort(Some(SortKey::Alpha))));
+        assert_eq!(parse_command("sort done-last"), Ok(Command::Sort(Some(SortKey::DoneLast))));
+        assert_eq!(parse_command("sort manual"), Ok(Command::Sort(None)));
+        assert_eq!(parse_command("clear-done"), Ok(Command::ClearDone));
+        assert_eq!(parse_command("clear-all"), Ok(Command::ClearAll));
+        assert_eq!(parse_command("export my list.md"), Ok(Command::Export("my list.md".to_string())));
+        assert_eq!(parse_command("export-outline out.txt"), Ok(Command::ExportOutline("out.txt".to_string())));
+        assert_eq!(parse_command("help"), Ok(Command::Help));
+        assert_eq!(parse_command("q"), Ok(Command::Quit));
+        assert_eq!(parse_command("quit"), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn parse_command_bad_input() {
+        assert_eq!(parse_command(""), Err("Empty command".to_string()));
+        assert_eq!(parse_command("   "), Err("Empty command".to_string()));
+        assert_eq!(parse_command("frobnicate"), Err("Unknown command: frobnicate".to_string()));
+        assert_eq!(parse_command("sort priority"), Err("Unknown sort key 'priority' (use alpha, done, due or none)".to_string()));
+        assert_eq!(parse_command("sort"), Err("Usage: sort <alpha|done|due|none>".to_string()));
+        assert_eq!(parse_command("export"), Err("Usage: export <path>".to_string()));
+        assert_eq!(parse_command("clear-done now"), Err("clear-done takes no arguments".to_string()));
+        assert_eq!(parse_command("quit please"), Err("quit takes no arguments".to_string()));
+    }
+
+    /// Texts with separators written as `--- label`.
+    fn outline_texts(tasks: &[Task]) -> Vec<String> {
+        tasks.iter().map(|task| if task.separator { format!("--- {}", task.text) } else { task.text.clone() }).collect()
+    }
+
+    #[test]
+    fn outline_round_trip() {
+        let tasks = store_with(&["top", "--- Work ---", "a", "b", "--", "c"]).load().tasks;
+        let outline = export_outline(&tasks);
+        assert_eq!(outline, "top\nWork\n  a\n  b\nc\n");
+        let mut imported = Vec::new();
+        assert_eq!(merge_outline(&mut imported, &mut 0, &parse_outline(&outline)), (4, 0));
+        assert_eq!(outline_texts(&imported), outline_texts(&tasks));
+        assert_eq!(export_outline(&imported), outline);
+    }
+
+    #[test]
+    fn parse_outline_flattens_deep_and_tab_indents() {
+        let sections = parse_outline("# plan\nHome\n\tfix tap\n      buy washer\n\nloose\nWork\n  report\n");
+        assert_eq!(
+            sections,
+            [
+                OutlineSection { heading: Some("Home"), items: vec!["fix tap", "buy washer"] },
+                OutlineSection { heading: None, items: vec!["loose"] },
+                OutlineSection { heading: Some("Work"), items: vec!["report"] },
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_outline_skips_tasks_already_listed() {
+        let mut tasks = store_with(&["report"]).load().tasks;
+        let sections = parse_outline("Work\n  report\n  report\nHome\n  fix tap\n");
+        assert_eq!(merge_outline(&mut tasks, &mut 0, &sections), (1, 2));
+        // Work had nothing new, so its heading isn't added
+        assert_eq!(outline_texts(&tasks), ["report", "--- Home", "fix tap"]);
+    }
+
+    #[test]
+    fn completions_per_day_counts_empty_days_as_zero() {
+        let today = day("2024-03-05");
+        let mut tasks = store_with(&["a", "b", "c", "open"]).load().tasks;
+        tasks[0].toggle_done(at("2024-03-05", "08:00"));
+        tasks[1].toggle_done(at("2024-03-05", "09:00"));
+        tasks[2].toggle_done(at("2024-03-03", "12:00"));
+        assert_eq!(completions_per_day(&tasks, today, 4), [0, 1, 0, 2]);
+        assert_eq!(completions_per_day(&[], today, 3), [0, 0, 0]);
+    }
+
+    #[test]
+    fn completions_per_day_splits_at_midnight() {
+        let today = day("2024-03-05");
+        let mut tasks = store_with(&["late", "early", "old", "future"]).load().tasks;
+        tasks[0].toggle_done(at("2024-03-04", "23:59"));
+        tasks[1].toggle_done(at("2024-03-05", "00:00"));
+        // Outside the window either way
+        tasks[2].toggle_done(at("2024-03-03", "23:59"));
+        tasks[3].toggle_done(at("2024-03-06", "00:00"));
+        assert_eq!(completions_per_day(&tasks, today, 2), [1, 1]);
+    }
+
+    #[test]
+    fn completions_per_day_skips_reopened_and_unstamped_tasks() {
+        let today = day("2024-03-05");
+        let mut tasks = store_with(&["reopened", "unstamped"]).load().tasks;
+        tasks[0].completed_at = Some(at("2024-03-05", "08:00"));
+        tasks[1].done = true;
+        assert_eq!(completions_per_day(&tasks, today, 1), [0]);
+    }
+
+    #[test]
+    fn stats_count_day_and_week_boundaries() {
+        // 2024-03-05 is a Tuesday, so the week began on Monday the 4th
+        let now = at("2024-03-05", "12:00");
+        let mut tasks = store_with(&["today", "monday", "sunday", "open", "late", "--- sep"]).load().tasks;
+        tasks[0].toggle_done(at("2024-03-05", "00:00"));
+        tasks[1].toggle_done(at("2024-03-04", "00:00"));
+        tasks[2].toggle_done(at("2024-03-03", "23:59"));
+        tasks[3].estimate_mins = Some(60);
+        tasks[3].time_spent_secs = 1500;
+        tasks[3].in_progress = true;
+        tasks[4].due = Some(day("2024-03-04"));
+        let stats = Stats::compute(&tasks, now);
+        assert_eq!((stats.total, stats.done, stats.overdue, stats.in_progress), (5, 3, 1, 1));
+        assert_eq!((stats.completed_today, stats.completed_this_week), (1, 2));
+        assert_eq!((stats.estimated_secs, stats.remaining_secs), (3600, 2100));
+        assert_eq!(stats.lines()[1], "Done:                3 (60%)");
+    }
+
+    #[test]
+    fn stats_of_an_empty_list() {
+        let stats = Stats::compute(&[], at("2024-03-05", "12:00"));
+        assert_eq!(stats.total, 0);
+        assert_eq!(stats.lines()[1], "Done:                0 (0%)");
+    }
+
+    /// `month_grid` as day numbers, 0 for cells outside the month.
+    fn grid_days(date: &str) -> Vec<[u32; 7]> {
+        month_grid(day(date)).iter().map(|week| week.map(|cell| cell.map_or(0, |date| date.day()))).collect()
+    }
+
+    #[test]
+    fn month_grid_leap_february() {
+        // 2024-02-01 is a Thursday
+        let weeks = grid_days("2024-02-15");
+        assert_eq!(weeks.len(), 5);
+        assert_eq!(weeks[0], [0, 0, 0, 1, 2, 3, 4]);
+        assert_eq!(weeks[4], [26, 27, 28, 29, 0, 0, 0]);
+        let last = grid_days("2023-02-01");
+        assert_eq!(last.last().unwrap().iter().max(), Some(&28));
+    }
+
+    #[test]
+    fn month_grid_month_starting_on_monday() {
+        // February 2021 starts on a Monday and fits in exactly four weeks
+        let weeks = grid_days("2021-02-10");
+        assert_eq!(weeks.len(), 4);
+        assert_eq!(weeks[0], [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(weeks[3], [22, 23, 24, 25, 26, 27, 28]);
+    }
+
+    #[test]
+    fn month_grid_month_starting_on_sunday() {
+        // September 2024 starts on a Sunday, in the last column
+        let weeks = grid_days("2024-09-30");
+        assert_eq!(weeks.len(), 6);
+        assert_eq!(weeks[0], [0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(weeks[5], [30, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn reset_due_daily_rolls_over_at_midnight() {
+        let last = day("2024-03-05");
+        assert!(!reset_due(last, day("2024-03-05"), ResetPeriod::Daily));
+        assert!(reset_due(last, day("2024-03-06"), ResetPeriod::Daily));
+        assert!(reset_due(last, day("2024-04-20"), ResetPeriod::Daily));
+        // A clock that went back doesn't reset
+        assert!(!reset_due(last, day("2024-03-04"), ResetPeriod::Daily));
+    }
+
+    #[test]
+    fn reset_due_weekly_rolls_over_on_monday() {
+        // Tuesday 2024-03-05; the next week starts Monday the 11th
+        let last = day("2024-03-05");
+        assert!(!reset_due(last, day("2024-03-10"), ResetPeriod::Weekly));
+        assert!(reset_due(last, day("2024-03-11"), ResetPeriod::Weekly));
+        // Last reset on a Sunday: the next day is a new week
+        assert!(reset_due(day("2024-03-10"), day("2024-03-11"), ResetPeriod::Weekly));
+        assert!(!reset_due(day("2024-03-11"), day("2024-03-17"), ResetPeriod::Weekly));
+        // Across the new year
+        assert!(reset_due(day("2024-12-31"), day("2025-01-06"), ResetPeriod::Weekly));
+        assert!(!reset_due(day("2024-12-31"), day("2025-01-05"), ResetPeriod::Weekly));
+    }
+
+    #[test]
+    fn parse_reset_period_names() {
+        assert_eq!(parse_reset_period("daily"), Some(ResetPeriod::Daily));
+        assert_eq!(parse_reset_period("weekly"), Some(ResetPeriod::Weekly));
+        assert_eq!(parse_reset_period("none"), None);
+        assert_eq!(parse_reset_period("monthly"), None);
+    }
+
+    #[test]
+    fn is_duplicate_case() {
+        let tasks = store_with(&["Buy Milk"]).load().tasks;
+        assert!(is_duplicate(&tasks, "Buy Milk", false));
+        assert!(!is_duplicate(&tasks, "buy milk", false));
+        assert!(is_duplicate(&tasks, "buy milk", true));
+        assert!(is_duplicate(&tasks, "BUY MILK", true));
+    }
+
+    #[test]
+    fn is_duplicate_whitespace() {
+        let mut tasks = store_with(&["buy milk"]).load().tasks;
+        assert!(is_duplicate(&tasks, "  buy milk\t", false));
+        // Only the ends are trimmed
+        assert!(!is_duplicate(&tasks, "buy  milk", false));
+        tasks[0].text = " buy milk ".to_string();
+        assert!(is_duplicate(&tasks, "buy milk", false));
+    }
+
+    #[test]
+    fn is_duplicate_done_and_open_alike() {
+        let mut tasks = store_with(&["buy milk", "call mom"]).load().tasks;
+        tasks[0].toggle_done(at("2024-03-05", "10:00"));
+        assert!(is_duplicate(&tasks, "buy milk", false));
+        assert!(is_duplicate(&tasks, "call mom", false));
+    }
+
+    #[test]
+    fn is_duplicate_separators_only_match_separators() {
+        let tasks = store_with(&["Work", "--- Home ---"]).load().tasks;
+        assert!(!is_duplicate(&tasks, "--- Work ---", false));
+        assert!(is_duplicate(&tasks, "-- Home", false));
+        assert!(!is_duplicate(&tasks, "Home", false));
+    }
+
+    #[test]
+    fn journal_lists_the_day_in_completion_order() {
+        let date = day("2024-03-05");
+        let mut tasks = store_with(&["second", "other day", "first", "open", "reopened"]).load().tasks;
+        tasks[0].toggle_done(at("2024-03-05", "15:00"));
+        tasks[1].toggle_done(at("2024-03-04", "23:59"));
+        tasks[2].toggle_done(at("2024-03-05", "00:00"));
+        tasks[4].completed_at = Some(at("2024-03-05", "12:00"));
+        assert_eq!(journal(&tasks, date), "## 2024-03-05 (Tuesday)\n- first\n- second\n");
+    }
+
+    #[test]
+    fn journal_of_a_day_with_nothing_done() {
+        let mut tasks = store_with(&["unstamped"]).load().tasks;
+        tasks[0].done = true;
+        assert_eq!(journal(&tasks, day("2024-03-10")), "## 2024-03-10 (Sunday)\n");
+    }
+
+    #[test]
+    fn start_only_clears_every_other_task_in_progress() {
+        let now = at("2024-03-05", "10:00");
+        let mut tasks = store_with(&["a", "b", "c", "d"]).load().tasks;
+        tasks[0].in_progress = true;
+        tasks[3].in_progress = true;
+        assert_eq!(start_only(&mut tasks, 2, now), 2);
+        assert_eq!(tasks.iter().map(Task::status).collect::<Vec<_>>(), [Status::Todo, Status::Todo, Status::InProgress, Status::Todo]);
+    }
+
+    #[test]
+    fn start_only_on_the_task_already_in_progress() {
+        let now = at("2024-03-05", "10:00");
+        let mut tasks = store_with(&["a", "b"]).load().tasks;
+        tasks[1].in_progress = true;
+        assert_eq!(start_only(&mut tasks, 1, now), 0);
+        assert_eq!(tasks[1].status(), Status::InProgress);
+    }
+
+    #[test]
+    fn start_only_reopens_a_done_task() {
+        let now = at("2024-03-05", "10:00");
+        let mut tasks = store_with(&["a"]).load().tasks;
+        tasks[0].toggle_done(now);
+        assert_eq!(start_only(&mut tasks, 0, now), 0);
+        assert_eq!(tasks[0].status(), Status::InProgress);
+        assert_eq!(tasks[0].completed_at, None);
+    }
+
+    #[test]
+    fn cycle_status_goes_round() {
+        let now = at("2024-03-05", "10:00");
+        let mut task = Task::new(1, "a");
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            task.cycle_status(now);
+            seen.push(task.status());
+        }
+        assert_eq!(seen, [Status::InProgress, Status::Done, Status::Todo, Status::InProgress]);
+    }
+
+    #[test]
+    fn check_accepts_a_missing_or_ordinary_file() {
+        let dir = temp_dir("check-ok");
+        let store = FileStore::new(dir.join("todos.json"));
+        assert_eq!(store.check(), Ok(()));
+        std::fs::write(dir.join("todos.json"), "[]").unwrap();
+        assert_eq!(store.check(), Ok(()));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn check_refuses_a_directory() {
+        let dir = temp_dir("check-dir");
+        std::fs::create_dir(dir.join("todos.json")).unwrap();
+        let err = FileStore::new(dir.join("todos.json")).check().unwrap_err();
+        assert!(err.contains("is a directory"), "{}", err);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn check_refuses_a_path_no_file_can_be_made_at() {
+        let dir = temp_dir("check-unwritable");
+        std::fs::write(dir.join("file"), "").unwrap();
+        let mut store = FileStore::new(dir.join("file").join("todos.json"));
+        let err = store.check().unwrap_err();
+        assert!(err.starts_with("Can't use"), "{}", err);
+        assert!(store.save(&[], 0).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_refuses_a_broken_symlink() {
+        let dir = temp_dir("check-symlink");
+        std::os::unix::fs::symlink(dir.join("gone.json"), dir.join("todos.json")).unwrap();
+        let err = FileStore::new(dir.join("todos.json")).check().unwrap_err();
+        assert!(err.contains("can't be followed"), "{}", err);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,250 +1,3757 @@
+use gottodo::*;
+use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
-use crossterm::{terminal, event::{self, Event, KeyCode, KeyModifiers}};
-use ratatui::{Terminal, backend::CrosstermBackend, widgets::{Block, Borders, List, ListItem, Paragraph}, layout::{Layout, Constraint, Direction}};
-use std::io::stdout;
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate};
+use crossterm::{execute, terminal::{self, EnterAlternateScreen, LeaveAlternateScreen}, event::{self, Event, KeyCode, KeyModifiers}};
+use ratatui::{Frame, Terminal, backend::CrosstermBackend, widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Sparkline, Wrap}, layout::{Alignment, Layout, Constraint, Direction, Rect}, style::{Color, Modifier, Style}, text::{Line, Span}};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write, stdout};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
 use std::env;
+use std::ffi::OsString;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::process::{self, Stdio};
+use std::time::{Duration, Instant};
 
-#[derive(Serialize, Deserialize, Clone)]
-struct Task {
-    text: String,
-    done: bool,
-}
+/// Minimum time between two writes of `todos.json`.
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+/// How long a typed task number waits for more digits before jumping.
+const NUMBER_TIMEOUT: Duration = Duration::from_millis(1200);
+/// How long typed letters keep building one typeahead prefix.
+const TYPEAHEAD_TIMEOUT: Duration = Duration::from_millis(1000);
+/// How long a toast stays in the status bar.
+const TOAST_DURATION: Duration = Duration::from_secs(2);
+/// Smallest terminal the layout is drawn in; anything smaller gets a notice instead.
+const MIN_WIDTH: u16 = 32;
+const MIN_HEIGHT: u16 = 7;
+/// The same for `--mini`, which has no borders to fit
+const MINI_MIN_WIDTH: u16 = 16;
+const MINI_MIN_HEIGHT: u16 = 3;
+/// Days of completions charted in the statistics overlay, today last.
+const SPARKLINE_DAYS: usize = 30;
+
+/// How long `x` puts off the reminder that just went off.
+const REMINDER_SNOOZE_MINS: i64 = 60;
+
+/// Where `:clear-all` keeps the tasks it removed.
+const CLEARED_FILE: &str = "todos.json.cleared";
+
+/// Height of the debug pane, which is dropped when it won't fit.
+const DEBUG_PANE_HEIGHT: u16 = 8;
 
 #[derive(PartialEq)]
 enum AppMode {
     Normal,
     AddingTask,
     ConfirmingDelete,
+    ConfirmingDeleteMarked,
     ShowingHelp,
+    ShowingStats,
+    Searching,
+    CommandMode,
+    ConfirmingReplace,
+    ConfirmingClearAll,
+    SettingDue,
+    SettingReminder,
+    Snoozing,
+    ConfirmingDuplicate,
+    SettingEstimate,
+    ShowingCalendar,
+    EditingText,
+    ConfirmingQuit,
+    ConfirmingReload,
 }
 
-fn load_tasks() -> Vec<Task> {
-    serde_json::from_reader(std::fs::File::open("todos.json").unwrap_or_else(|_| {
-        std::fs::File::create("todos.json").unwrap()
-    })).unwrap_or_else(|_| vec![])
+/// User settings read from `config.toml`; missing keys fall back to defaults.
+#[derive(Deserialize)]
+#[serde(default)]
+struct Config {
+    pomodoro_minutes: u64,
+    break_minutes: u64,
+    pomodoro_notify: bool,
+    /// Ring the terminal bell when a session or break ends
+    pomodoro_bell: bool,
+    /// Desktop notification as well as the toast when a task reminder goes off
+    reminder_notify: bool,
+    line_numbers: bool,
+    /// Delete tasks that were completed more than this many days ago on startup
+    auto_purge_days: Option<u32>,
+    /// Ask before adding a task whose text matches an existing one
+    warn_duplicates: bool,
+    duplicate_ignore_case: bool,
+    /// Sort applied once on startup: `none` keeps the file order
+    default_sort: String,
+    /// Category name to colour name, e.g. `work = "blue"`
+    categories: BTreeMap<String, String>,
+    /// Append every change to `events.jsonl`
+    event_log: bool,
+    /// Markers drawn before open, started and done tasks
+    checkbox_unchecked: String,
+    checkbox_in_progress: String,
+    checkbox_checked: String,
+    /// Uncheck every task on the first start of each `daily` or `weekly` period; `none` is off
+    reset_period: String,
+    /// Up on the first task goes to the last and Down on the last to the first
+    wrap_navigation: bool,
+    /// Ask before `d` deletes; when off the deletion can still be undone with `u`
+    confirm_delete: bool,
+    /// Ask whether to keep the running timer's time before quitting
+    confirm_quit_with_timer: bool,
+    /// `compact` draws a task per line; `spacious` gives each task a second line for its details
+    density: String,
+    /// Refuse to mark a task done while a task it waits on is still open
+    strict_blocking: bool,
+    /// strftime-style pattern for dates in the list, e.g. `%d.%m.%Y`
+    date_format: String,
+    /// Longest task text, in characters, that can be added; unlimited when unset
+    max_task_length: Option<usize>,
+    /// What happens to text over `max_task_length`: `reject` it or `truncate` it
+    long_task_text: String,
+    /// Style `*bold*`, `_italic_` and `` `code` `` in task text instead of showing the markers
+    inline_markup: bool,
 }
 
-fn save_tasks(tasks: &[Task]) {
-    serde_json::to_writer_pretty(std::fs::File::create("todos.json").unwrap(), tasks).unwrap();
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            pomodoro_minutes: 25,
+            break_minutes: 5,
+            pomodoro_notify: false,
+            pomodoro_bell: false,
+            reminder_notify: false,
+            line_numbers: false,
+            auto_purge_days: None,
+            warn_duplicates: false,
+            duplicate_ignore_case: true,
+            default_sort: "none".to_string(),
+            categories: BTreeMap::new(),
+            event_log: false,
+            checkbox_unchecked: "[ ]".to_string(),
+            checkbox_in_progress: "[~]".to_string(),
+            checkbox_checked: "[x]".to_string(),
+            reset_period: "none".to_string(),
+            wrap_navigation: false,
+            confirm_delete: true,
+            confirm_quit_with_timer: true,
+            density: "compact".to_string(),
+            strict_blocking: false,
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            max_task_length: None,
+            long_task_text: "reject".to_string(),
+            inline_markup: false,
+        }
+    }
 }
 
-fn main() -> std::io::Result<()> {
-    let debug_mode = env::args().any(|arg| arg == "--debug");
-    
-    let stdout = stdout();
-    terminal::enable_raw_mode()?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+impl Config {
+    /// Puts back the default for any blank checkbox glyph, returning a warning
+    /// naming the ones that were replaced.
+    fn fix_checkboxes(&mut self) -> Option<String> {
+        let defaults = Config::default();
+        let mut blank = Vec::new();
+        if self.checkbox_unchecked.trim().is_empty() {
+            self.checkbox_unchecked = defaults.checkbox_unchecked;
+            blank.push("checkbox_unchecked");
+        }
+        if self.checkbox_in_progress.trim().is_empty() {
+            self.checkbox_in_progress = defaults.checkbox_in_progress;
+            blank.push("checkbox_in_progress");
+        }
+        if self.checkbox_checked.trim().is_empty() {
+            self.checkbox_checked = defaults.checkbox_checked;
+            blank.push("checkbox_checked");
+        }
+        (!blank.is_empty()).then(|| format!("{} can't be empty; using the default", blank.join(" and ")))
+    }
 
-    let mut tasks = load_tasks();
-    let mut selected = 0;
-    let mut ui_visible = true;
-    let mut debug_log: Vec<String> = Vec::new();
-    let mut app_mode = AppMode::Normal;
-    let mut input_text = String::new();
-    
-    if debug_mode {
-        debug_log.push("Debug mode enabled".to_string());
-        debug_log.push(format!("UI visible: {}", ui_visible));
+    /// `text` checked against `max_task_length`: Ok with the text to store and
+    /// whether it was cut short, or Err with why it was turned away.
+    fn fit_task_text(&self, text: &str) -> Result<(String, bool), String> {
+        let Some(max) = self.max_task_length else { return Ok((text.to_string(), false)) };
+        match truncate_chars(text, max) {
+            None => Ok((text.to_string(), false)),
+            Some(cut) if self.long_task_text == "truncate" => Ok((cut, true)),
+            Some(_) => Err(format!("Too long: {} characters, the limit is {}", text.chars().count(), max)),
+        }
     }
 
-    loop {
-        terminal.draw(|f| {
-            let size = f.size();
-            
-            // Create main layout (with prompt area, debug area)
-            let main_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(match (app_mode != AppMode::Normal && app_mode != AppMode::ShowingHelp, debug_mode) {
-                    (true, true) => [Constraint::Min(8), Constraint::Length(3), Constraint::Length(8)].as_ref(),
-                    (true, false) => [Constraint::Min(8), Constraint::Length(3)].as_ref(),
-                    (false, true) => [Constraint::Min(10), Constraint::Length(8)].as_ref(),
-                    (false, false) => [Constraint::Min(0)].as_ref(),
-                })
-                .split(size);
-            
-            // Only render todo list if UI is visible
-            if ui_visible {
-                // Main content area (todo list)
-                let content_chunks = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([Constraint::Min(60), Constraint::Length(30)].as_ref())
-                    .split(main_chunks[0]);
-
-                let items: Vec<ListItem> = tasks.iter().enumerate().map(|(i, task)| {
-                    let prefix = if task.done { "[x]" } else { "[ ]" };
-                    let style = if i == selected { 
-                        ratatui::style::Style::default().bg(ratatui::style::Color::Blue) 
-                    } else { 
-                        ratatui::style::Style::default() 
-                    };
-                    ListItem::new(format!("{} {}", prefix, task.text)).style(style)
-                }).collect();
-
-                let title = if app_mode == AppMode::Normal {
-                    "TODO (h=help)"
-                } else {
-                    "TODO"
-                };
-                let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
-                f.render_widget(list, content_chunks[1]);
-            }
-            
-            // Prompt area for input/confirmation (not for help mode)
-            if app_mode != AppMode::Normal && app_mode != AppMode::ShowingHelp {
-                let prompt_text = match app_mode {
-                    AppMode::AddingTask => format!("Add task: {}", input_text),
-                    AppMode::ConfirmingDelete => {
-                        if !tasks.is_empty() && selected < tasks.len() {
-                            format!("Delete '{}' ? (y/n)", tasks[selected].text)
-                        } else {
-                            "No task to delete".to_string()
+    /// Puts back the ISO default if `date_format` can't format a date, so a
+    /// bad pattern is reported once instead of failing at every redraw.
+    fn fix_date_format(&mut self) -> Option<String> {
+        if format_date(NaiveDate::default(), &self.date_format).is_some() {
+            return None;
+        }
+        let warning = format!("date_format '{}' isn't a valid date pattern; using {}", self.date_format, DEFAULT_DATE_FORMAT);
+        self.date_format = DEFAULT_DATE_FORMAT.to_string();
+        Some(warning)
+    }
+
+    /// `date` as the user asked for it in `date_format`.
+    fn date(&self, date: NaiveDate) -> String {
+        format_date(date, &self.date_format).unwrap_or_else(|| date.to_string())
+    }
+
+    /// The marker for a task, padded so every glyph takes the same number of
+    /// columns and task text stays aligned.
+    fn checkbox(&self, status: Status) -> String {
+        let glyphs = [&self.checkbox_unchecked, &self.checkbox_in_progress, &self.checkbox_checked];
+        let width = glyphs.iter().map(|glyph| glyph.width()).max().unwrap_or(0);
+        let glyph = match status {
+            Status::Todo => &self.checkbox_unchecked,
+            Status::InProgress => &self.checkbox_in_progress,
+            Status::Done => &self.checkbox_checked,
+        };
+        format!("{}{}", glyph, " ".repeat(width - glyph.width()))
+    }
+}
+
+/// Session state kept in `ui_state.json` between runs.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct UiState {
+    selected_index: usize,
+    selected_id: Option<u64>,
+    /// When `reset_period` last unchecked the list
+    last_reset: Option<NaiveDate>,
+}
+
+/// Display toggles and the active view kept in `prefs.json` so they survive
+/// restarts. Filters are checked against the list again on startup.
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone)]
+#[serde(default)]
+struct Prefs {
+    show_details: bool,
+    agenda_view: bool,
+    done_last_view: bool,
+    group_view: bool,
+    collapsed_groups: BTreeSet<String>,
+    focus_mode: bool,
+    snoozed_view: bool,
+    overdue_view: bool,
+    category_filter: Option<String>,
+    day_filter: Option<NaiveDate>,
+    split_view: bool,
+    focused_pane: Pane,
+    /// The `:sort` key picked in the app, `none` for the list's own order;
+    /// unset while it matches `default_sort`, so the config still applies
+    sort_view: Option<String>,
+    /// Row spacing toggled with `V`, unset while it matches `density`
+    spacious: Option<bool>,
+}
+
+/// The pane of the inbox/scheduled split view that Up/Down and task actions
+/// apply to.
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+enum Pane {
+    #[default]
+    Inbox,
+    Scheduled,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum PomodoroPhase {
+    Work,
+    Break,
+}
+
+struct Pomodoro {
+    task_id: u64,
+    phase: PomodoroPhase,
+    remaining: Duration,
+    last_tick: Instant,
+}
+
+/// The multi-line editor `E` opens on a task's text. Lines wrap at the
+/// width of the last frame and Up/Down move between the wrapped rows.
+struct TextArea {
+    chars: Vec<char>,
+    /// Char index the next typed character goes in at
+    cursor: usize,
+    /// Columns inside the border as of the last frame
+    width: usize,
+}
+
+impl TextArea {
+    fn new(text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        TextArea { cursor: chars.len(), chars, width: 0 }
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    /// Start and end char index of each row on screen; a row ends at a
+    /// newline (not included) or where the next character would overflow.
+    fn rows(&self) -> Vec<(usize, usize)> {
+        let width = self.width.max(1);
+        let mut rows = Vec::new();
+        let (mut start, mut column) = (0, 0);
+        for (i, c) in self.chars.iter().enumerate() {
+            if *c == '\n' {
+                rows.push((start, i));
+                (start, column) = (i + 1, 0);
+                continue;
+            }
+            let char_width = c.width().unwrap_or(0);
+            if column + char_width > width && i > start {
+                rows.push((start, i));
+                (start, column) = (i, 0);
+            }
+            column += char_width;
+        }
+        rows.push((start, self.chars.len()));
+        rows
+    }
+
+    /// The row and column the cursor is drawn at.
+    fn cursor_position(&self) -> (usize, usize) {
+        let rows = self.rows();
+        let row = rows.iter().rposition(|(start, _)| *start <= self.cursor).unwrap_or(0);
+        let start = rows[row].0;
+        (row, self.chars[start..self.cursor].iter().map(|c| c.width().unwrap_or(0)).sum())
+    }
+
+    /// Moves the cursor a row up or down, keeping to its column where the row is long enough.
+    fn move_vertically(&mut self, down: bool) {
+        let rows = self.rows();
+        let (row, column) = self.cursor_position();
+        let target = if down { row + 1 } else { row.wrapping_sub(1) };
+        let Some(&(start, _)) = rows.get(target) else { return };
+        let last = Self::row_end(&rows, target);
+        let mut position = start;
+        let mut filled = 0;
+        while position < last {
+            let char_width = self.chars[position].width().unwrap_or(0);
+            if filled + char_width > column {
+                break;
+            }
+            filled += char_width;
+            position += 1;
+        }
+        self.cursor = position;
+    }
+
+    /// Applies an editing or movement key. Returns false for keys it doesn't use.
+    fn handle(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char(c) => {
+                self.chars.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Enter => {
+                self.chars.insert(self.cursor, '\n');
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.chars.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.chars.len() => {
+                self.chars.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.chars.len()),
+            KeyCode::Up => self.move_vertically(false),
+            KeyCode::Down => self.move_vertically(true),
+            KeyCode::Home => {
+                let (row, _) = self.cursor_position();
+                self.cursor = self.rows()[row].0;
+            }
+            KeyCode::End => {
+                let (row, _) = self.cursor_position();
+                self.cursor = Self::row_end(&self.rows(), row);
+            }
+            KeyCode::Backspace | KeyCode::Delete => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// The last cursor position on `row`. A wrapped row's end is where the
+    /// next one starts, so it stops a character short.
+    fn row_end(rows: &[(usize, usize)], row: usize) -> usize {
+        let (start, end) = rows[row];
+        let wrapped = rows.get(row + 1).is_some_and(|(next, _)| *next == end && end > start);
+        if wrapped { end - 1 } else { end }
+    }
+}
+
+fn load_ui_state() -> UiState {
+    std::fs::read_to_string("ui_state.json")
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_ui_state(state: &UiState) {
+    if let Ok(file) = std::fs::File::create("ui_state.json") {
+        let _ = serde_json::to_writer_pretty(file, state);
+    }
+}
+
+fn load_prefs() -> Prefs {
+    std::fs::read_to_string("prefs.json")
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_prefs(prefs: &Prefs) {
+    if let Ok(file) = std::fs::File::create("prefs.json") {
+        let _ = serde_json::to_writer_pretty(file, prefs);
+    }
+}
+
+/// Finds the previously selected task again by id, so reordering the file
+/// doesn't lose the place. If that task is gone the old index is used,
+/// clamped to the list.
+fn restore_selection(tasks: &[Task], state: &UiState) -> usize {
+    state
+        .selected_id
+        .and_then(|id| index_of(tasks, id))
+        .unwrap_or_else(|| state.selected_index.min(tasks.len().saturating_sub(1)))
+}
+
+/// Where to read settings from, most specific first: `--config <path>`, then
+/// `GOTTODO_CONFIG`, then `config.toml` in the current directory if there is
+/// one, then `$XDG_CONFIG_HOME/gottodo/config.toml` (`~/.config` when unset).
+/// The flag's and variable's paths count as explicit, so a missing file there
+/// is reported instead of quietly using defaults. Environment variables come
+/// from `var` and the current directory is `cwd`, so the lookup can be tried
+/// out without touching the process's own.
+fn config_path(flag: Option<&str>, var: impl Fn(&str) -> Option<OsString>, cwd: &Path) -> (PathBuf, bool) {
+    if let Some(path) = flag {
+        return (PathBuf::from(path), true);
+    }
+    if let Some(path) = var("GOTTODO_CONFIG").filter(|path| !path.is_empty()) {
+        return (PathBuf::from(path), true);
+    }
+    let local = cwd.join("config.toml");
+    if local.exists() {
+        return (local, false);
+    }
+    let config_home = var("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")));
+    match config_home {
+        Some(dir) => (dir.join("gottodo").join("config.toml"), false),
+        None => (local, false),
+    }
+}
+
+/// `config_path` for this process: its flag, environment and directory.
+fn process_config_path(flag: Option<&str>) -> (PathBuf, bool) {
+    config_path(flag, |name| env::var_os(name), &env::current_dir().unwrap_or_default())
+}
+
+/// Every setting with its default, commented out, as `init` writes it.
+const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+/// Creates an empty `todos.json` and a commented `config.toml` at the path
+/// `config_path` picks, then exits. An existing task list is never touched;
+/// an existing config is only replaced with `force`.
+fn init_files(config: &Path, force: bool) -> ! {
+    let mut refused = false;
+    if config.exists() && !force {
+        eprintln!("gottodo: {} already exists; pass --force to replace it", config.display());
+        refused = true;
+    } else {
+        let written = match config.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            Some(dir) => std::fs::create_dir_all(dir).and_then(|()| std::fs::write(config, DEFAULT_CONFIG)),
+            None => std::fs::write(config, DEFAULT_CONFIG),
+        };
+        if let Err(err) = written {
+            eprintln!("gottodo: can't write {}: {}", config.display(), err);
+            process::exit(1);
+        }
+        println!("Wrote settings to {}", config.display());
+    }
+    let data = Path::new("todos.json");
+    if data.exists() {
+        println!("Kept the existing {}", data.display());
+    } else if let Err(err) = std::fs::write(data, "[]\n") {
+        eprintln!("gottodo: can't write {}: {}", data.display(), err);
+        process::exit(1);
+    } else {
+        println!("Created an empty {} in the current directory", data.display());
+    }
+    process::exit(if refused { 1 } else { 0 });
+}
+
+/// Reads the config, falling back to defaults when the file is missing or
+/// broken. The second value explains any problem worth showing the user.
+fn load_config(path: &Path, explicit: bool) -> (Config, Option<String>) {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && !explicit => return (Config::default(), None),
+        Err(err) => return (Config::default(), Some(format!("Couldn't read {}: {}", path.display(), err))),
+    };
+    match toml::from_str::<Config>(&contents) {
+        Ok(mut config) => {
+            let warnings: Vec<String> = [config.fix_checkboxes(), config.fix_date_format()].into_iter().flatten().collect();
+            (config, (!warnings.is_empty()).then(|| warnings.join("; ")))
+        }
+        Err(err) => {
+            let line = err.span().map_or(1, |span| contents[..span.start].matches('\n').count() + 1);
+            let warning = format!("{} line {}: {}; using defaults", path.display(), line, err.message());
+            (Config::default(), Some(warning))
+        }
+    }
+}
+
+/// The accent colour for a category; unknown names and colours are gray.
+fn category_color(config: &Config, category: &str) -> Color {
+    config.categories.get(category).and_then(|name| name.parse().ok()).unwrap_or(Color::Gray)
+}
+
+/// What drawing task rows needs besides the rows themselves.
+struct RowContext<'a> {
+    tasks: &'a [Task],
+    config: &'a Config,
+    selected: usize,
+    selected_group: Option<&'a str>,
+    /// The group whose row of the selected task is the highlighted one
+    selected_in: Option<&'a str>,
+    active_timer: Option<(u64, Instant)>,
+    no_color: bool,
+    snoozed_view: bool,
+    show_details: bool,
+    /// Details go on a second line under the text instead of after it
+    spacious: bool,
+    now: DateTime<Local>,
+    /// Digits in the highest line number
+    number_width: usize,
+    /// Ids marked for a bulk action; rows get a `*` gutter while any are
+    marked: &'a HashSet<u64>,
+}
+
+/// The list items for `rows`, with task text cut to fit `area`, the inside
+/// of the list's block.
+fn list_items(rows: &[Row], area: Rect, ctx: &RowContext) -> Vec<ListItem<'static>> {
+    let inner_width = area.width as usize;
+    let mut number = 0;
+    let mut heading = None;
+    rows.iter().map(|row| {
+        let (i, positions) = match row {
+            Row::Header(title) => {
+                let style = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
+                return ListItem::new(Line::from(Span::styled(title.clone(), style)));
+            }
+            Row::Group(name, count, collapsed) => {
+                heading = Some(name.as_str());
+                let marker = if *collapsed { "▸" } else { "▾" };
+                let mut style = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
+                if ctx.selected_group == Some(name.as_str()) {
+                    style = if ctx.no_color { style.add_modifier(Modifier::REVERSED) } else { style.bg(Color::Blue) };
+                }
+                return ListItem::new(Line::from(Span::styled(format!("{} {} ({})", marker, name, count), style)));
+            }
+            Row::Task(i, positions) => (*i, positions),
+        };
+        number += 1;
+        let task = &ctx.tasks[i];
+        let cursor = i == ctx.selected && ctx.selected_group.is_none() && (ctx.selected_in.is_none() || heading == ctx.selected_in);
+        if task.separator {
+            let mut style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::BOLD);
+            if cursor {
+                style = if ctx.no_color { style.add_modifier(Modifier::REVERSED) } else { style.bg(Color::Blue) };
+            }
+            let label = if task.text.is_empty() { String::new() } else { format!(" {} ", task.text) };
+            let rule = "─".repeat(inner_width.saturating_sub(label.width()) / 2);
+            return ListItem::new(Line::from(Span::styled(format!("{}{}{}", rule, label, rule), style)));
+        }
+        let prefix = ctx.config.checkbox(task.status());
+        let mut style = if !cursor {
+            Style::default()
+        } else if ctx.no_color {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().bg(Color::Blue)
+        };
+        // The timed task stands out even when it isn't the selected one
+        let timing = ctx.active_timer.is_some_and(|(id, _)| id == task.id);
+        let blocker = open_blocker(ctx.tasks, task).filter(|_| !task.done);
+        if timing {
+            style = style.fg(Color::Green).add_modifier(Modifier::BOLD);
+        } else if blocker.is_some() {
+            style = style.fg(Color::DarkGray);
+        } else if task.status() == Status::InProgress {
+            style = style.fg(Color::Yellow);
+        }
+        let running = ctx.active_timer
+            .filter(|(id, _)| *id == task.id)
+            .map_or(0, |(_, started)| started.elapsed().as_secs());
+        let tracked = task.time_spent_secs + running;
+        let mut details: Vec<Span> = Vec::new();
+        if timing {
+            details.push(Span::raw(format!(" ⏱ {}", format_duration(running))));
+        }
+        if let (Some(due), Some(label)) = (task.due_at(), task.due_label(&ctx.config.date_format)) {
+            let style = match urgency_color(due, ctx.now.naive_local()).filter(|_| !task.done) {
+                Some(color) => Style::default().fg(color),
+                None => Style::default(),
+            };
+            details.push(Span::styled(format!(" 📅 {}", label), style));
+        }
+        match task.estimate_mins {
+            Some(estimate) => {
+                let over = tracked > estimate as u64 * 60;
+                let style = if over { Style::default().fg(Color::Red) } else { Style::default() };
+                let effort = format!(" ({}/{})", format_duration(tracked), format_duration(estimate as u64 * 60));
+                details.push(Span::styled(effort, style));
+            }
+            None if tracked > 0 => details.push(Span::raw(format!(" ({})", format_duration(tracked)))),
+            None => {}
+        }
+        if task.pomodoros > 0 {
+            details.push(Span::raw(format!(" 🍅{}", task.pomodoros)));
+        }
+        if let Some(interval) = task.reminder_interval_mins.filter(|_| !task.done) {
+            details.push(Span::raw(format!(" 🔔 {}", format_duration(interval as u64 * 60))));
+        }
+        if let Some(until) = task.snoozed_until.filter(|_| ctx.snoozed_view) {
+            details.push(Span::raw(format!(" 💤 {} {}", ctx.config.date(until.date_naive()), until.format("%H:%M"))));
+        }
+        if ctx.show_details && let Some(category) = &task.category {
+            details.push(Span::styled(format!(" {}", category), Style::default().fg(category_color(ctx.config, category))));
+        }
+        if ctx.show_details && let Some(blocker) = blocker {
+            let text = truncate_chars(&blocker.text, 24).unwrap_or_else(|| blocker.text.clone());
+            details.push(Span::raw(format!(" ⛔ {}", text)));
+        }
+        if ctx.show_details && let Some(created_at) = task.created_at {
+            details.push(Span::raw(format!(" · {}", relative_time(created_at, ctx.now))));
+        }
+        let prefix = if ctx.config.line_numbers {
+            format!("{:>width$}. {} ", number, prefix, width = ctx.number_width)
+        } else {
+            format!("{} ", prefix)
+        };
+        let prefix = match ctx.marked.is_empty() {
+            true => prefix,
+            false if ctx.marked.contains(&task.id) => format!("* {}", prefix),
+            false => format!("  {}", prefix),
+        };
+        let details_width: usize = if ctx.spacious { 0 } else { details.iter().map(Span::width).sum() };
+        let mut spans = vec![match task.status() {
+            Status::InProgress => Span::styled(prefix, Style::default().fg(Color::Yellow)),
+            _ => Span::raw(prefix),
+        }];
+        if timing {
+            spans.push(Span::raw("▶ "));
+        } else if blocker.is_some() {
+            spans.push(Span::raw("⛔ "));
+        }
+        if let Some(category) = &task.category {
+            spans.push(Span::styled("● ", Style::default().fg(category_color(ctx.config, category))));
+        }
+        let prefix_width: usize = spans.iter().map(Span::width).sum();
+        let text_width = inner_width.saturating_sub(prefix_width + details_width);
+        spans.extend(highlight_spans(&task.text, positions, text_width, ctx.config.inline_markup));
+        if ctx.spacious {
+            // Every task takes two lines, so the spacing stays even when one has no details
+            let mut detail_line = vec![Span::raw(" ".repeat(prefix_width.saturating_sub(1)))];
+            detail_line.extend(details);
+            return ListItem::new(vec![Line::from(spans), Line::from(detail_line)]).style(style);
+        }
+        spans.extend(details);
+        ListItem::new(Line::from(spans)).style(style)
+    }).collect()
+}
+
+/// Draws `items` in `block`, or `placeholder` in grey when there are none.
+fn render_list(f: &mut Frame, area: Rect, items: Vec<ListItem>, block: Block, placeholder: &str) {
+    if items.is_empty() {
+        let paragraph = Paragraph::new(placeholder)
+            .style(Style::default().fg(Color::DarkGray))
+            .wrap(Wrap { trim: true })
+            .block(block);
+        f.render_widget(paragraph, area);
+    } else {
+        f.render_widget(List::new(items).block(block), area);
+    }
+}
+
+/// Draws the text prompt, with the cursor after the input when `takes_text`.
+/// Without `bordered` it is a single bare line, for `--mini`.
+fn render_prompt(f: &mut Frame, area: Rect, mut text: String, error: Option<&str>, takes_text: bool, bordered: bool) {
+    let cursor_column = text.width() as u16;
+    if let Some(error) = error {
+        text.push_str(&format!("  ✗ {}", error));
+    }
+    let block = if bordered { Block::default().borders(Borders::ALL).title("Prompt") } else { Block::default() };
+    let inner = block.inner(area);
+    f.render_widget(Paragraph::new(text).block(block), area);
+    if takes_text {
+        let x = (inner.x + cursor_column).min(inner.right().saturating_sub(1));
+        f.set_cursor(x, inner.y);
+    }
+}
+
+/// Draws the help overlay from line `scroll`, first clamping it so the end of
+/// the help can't scroll up past the bottom. Returns how many lines fit.
+fn render_help(f: &mut Frame, area: Rect, scroll: &mut u16) -> u16 {
+    let lines = help_lines();
+    let height = area.height.saturating_sub(2);
+    *scroll = (*scroll).min((lines.len() as u16).saturating_sub(height));
+    let help_paragraph = Paragraph::new(lines)
+        .scroll((*scroll, 0))
+        .block(Block::default().borders(Borders::ALL).title("Help"));
+    f.render_widget(Clear, area);
+    f.render_widget(help_paragraph, area);
+    height
+}
+
+/// Draws the task editor over `area`, scrolled to keep the cursor in view.
+fn render_editor(f: &mut Frame, area: Rect, editor: &mut TextArea, error: Option<&str>) {
+    editor.width = area.width.saturating_sub(2) as usize;
+    let height = area.height.saturating_sub(2) as usize;
+    let (row, column) = editor.cursor_position();
+    let scroll = (row + 1).saturating_sub(height.max(1));
+    let lines: Vec<Line> = editor
+        .rows()
+        .into_iter()
+        .skip(scroll)
+        .take(height)
+        .map(|(start, end)| Line::from(editor.chars[start..end].iter().collect::<String>()))
+        .collect();
+    let title = match error {
+        Some(error) => format!("Edit task  ✗ {}", error),
+        None => "Edit task".to_string(),
+    };
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)), area);
+    f.set_cursor(area.x + 1 + column as u16, area.y + 1 + (row - scroll) as u16);
+}
+
+/// Draws the last few debug log entries.
+fn render_debug(f: &mut Frame, area: Rect, debug_log: &[String]) {
+    let debug_text = debug_log.iter().rev().take(6).rev().cloned().collect::<Vec<_>>().join("\n");
+    let debug_paragraph = Paragraph::new(debug_text)
+        .block(Block::default().borders(Borders::ALL).title("Debug Log"));
+
+    f.render_widget(debug_paragraph, area);
+}
+
+/// Everything one frame is drawn from, borrowed from the event loop.
+struct FrameState<'a> {
+    tasks: &'a [Task],
+    config: &'a Config,
+    app_mode: &'a AppMode,
+    mini: bool,
+    debug_mode: bool,
+    ui_visible: bool,
+    focus_mode: bool,
+    no_color: bool,
+    read_only: bool,
+    /// The rows of the list, or of the focused pane in the split view
+    rows: &'a [Row],
+    /// The unfocused pane's rows in the split view
+    other_rows: &'a [Row],
+    visible: &'a [usize],
+    selected: usize,
+    selected_id: Option<u64>,
+    /// The cursor is on a task rather than a heading or an empty list
+    has_selection: bool,
+    selected_group: Option<&'a str>,
+    selected_in: Option<&'a str>,
+    active_timer: Option<(u64, Instant)>,
+    snoozed_view: bool,
+    show_details: bool,
+    spacious: bool,
+    group_view: bool,
+    agenda_view: bool,
+    overdue_view: bool,
+    split_view: bool,
+    focused_pane: Pane,
+    sort_view: Option<SortKey>,
+    category_filter: Option<&'a str>,
+    day_filter: Option<NaiveDate>,
+    search_query: &'a str,
+    marked: &'a HashSet<u64>,
+    pending_block: Option<u64>,
+    pending_delete: Option<u64>,
+    pending_replace: Option<&'a (Replacement, usize)>,
+    dirty: bool,
+    saves_in_flight: usize,
+    load_warning: Option<&'a str>,
+    save_error: Option<&'a str>,
+    prompt_error: Option<&'a str>,
+    pomodoro: Option<&'a Pomodoro>,
+    number_input: Option<usize>,
+    typeahead: Option<&'a str>,
+    toast: Option<&'a str>,
+    add_due_today: bool,
+    input_text: &'a str,
+    now: DateTime<Local>,
+    today: NaiveDate,
+    calendar_day: NaiveDate,
+    debug_log: &'a [String],
+    help_scroll: &'a mut u16,
+    /// Set to how many help lines fit while the help is showing
+    help_height: &'a mut u16,
+    editor: Option<&'a mut TextArea>,
+}
+
+impl FrameState<'_> {
+    /// The list's frame; `--mini` draws the bare rows instead.
+    fn block(&self, title: String) -> Block<'static> {
+        if self.mini { Block::default() } else { Block::default().borders(Borders::ALL).title(title) }
+    }
+}
+
+/// Draws the whole frame: the list (or focus view), prompt, status bar,
+/// overlays and debug pane, or a notice when the terminal is too small.
+fn render(f: &mut Frame, state: &mut FrameState) {
+    let size = f.size();
+    let (min_width, min_height) = if state.mini { (MINI_MIN_WIDTH, MINI_MIN_HEIGHT) } else { (MIN_WIDTH, MIN_HEIGHT) };
+    if size.width < min_width || size.height < min_height {
+        let notice = format!("Terminal too small ({}x{}), need {}x{}", size.width, size.height, min_width, min_height);
+        f.render_widget(Paragraph::new(notice).wrap(Wrap { trim: true }), size);
+        return;
+    }
+    let show_prompt = !matches!(
+        state.app_mode,
+        AppMode::Normal | AppMode::ShowingHelp | AppMode::ShowingStats | AppMode::ShowingCalendar | AppMode::EditingText
+    );
+    let show_debug = state.debug_mode && !state.mini && size.height >= MIN_HEIGHT + DEBUG_PANE_HEIGHT;
+
+    // Create main layout (content, optional prompt, status bar, optional debug area)
+    let mut constraints = vec![Constraint::Min(0)];
+    if show_prompt {
+        constraints.push(Constraint::Length(if state.mini { 1 } else { 3 }));
+    }
+    constraints.push(Constraint::Length(1));
+    if show_debug {
+        constraints.push(Constraint::Length(DEBUG_PANE_HEIGHT));
+    }
+    let main_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(size);
+    let status_index = if show_prompt { 2 } else { 1 };
+
+    // Focus mode replaces the list with just the selected task
+    if state.ui_visible && state.focus_mode {
+        render_focus(f, main_chunks[0], state);
+    } else if state.ui_visible {
+        render_tasks(f, main_chunks[0], state);
+    }
+
+    if state.ui_visible {
+        render_status(f, main_chunks[status_index], state);
+    }
+
+    // Prompt area for input/confirmation (not for help mode)
+    if show_prompt {
+        let input_text = state.input_text;
+        let prompt_text = match state.app_mode {
+            AppMode::AddingTask if state.add_due_today => format!("Add task due today: {}", input_text),
+            AppMode::AddingTask => format!("Add task: {}", input_text),
+            AppMode::CommandMode => format!(":{}", input_text),
+            AppMode::SettingDue => format!("Due (YYYY-MM-DD [HH:MM], today, +3d): {}", input_text),
+            AppMode::SettingEstimate => format!("Estimate (90, 90m, 2h, 1h30m): {}", input_text),
+            AppMode::SettingReminder => format!("Remind every (30, 90m, 1h): {}", input_text),
+            AppMode::Snoozing => format!("Snooze until (YYYY-MM-DD, tomorrow, +3d, +2h): {}", input_text),
+            AppMode::Searching => format!("Search: {}", state.search_query),
+            AppMode::ConfirmingReplace => match state.pending_replace {
+                Some((replacement, count)) => format!(
+                    "Replace '{}' with '{}' in {} task(s)? (y/n)",
+                    replacement.find, replacement.replace, count
+                ),
+                None => "Nothing to replace".to_string(),
+            },
+            AppMode::ConfirmingClearAll => format!("Type yes to delete all {} task(s): {}", state.tasks.len(), input_text),
+            AppMode::ConfirmingDuplicate => "Task already exists — add anyway? (y/n)".to_string(),
+            AppMode::ConfirmingDelete => match state.pending_delete.and_then(|id| index_of(state.tasks, id)) {
+                Some(index) => format!("Delete '{}' ? (y/n)", state.tasks[index].text),
+                None => "No task to delete".to_string(),
+            },
+            AppMode::ConfirmingDeleteMarked => format!("Delete {} marked task(s)? (y/n)", state.marked.len()),
+            AppMode::ConfirmingQuit => "Timer running — stop and save time before quitting? (y/n)".to_string(),
+            AppMode::ConfirmingReload => "Discard unsaved changes and reload from disk? (y/n)".to_string(),
+            AppMode::Normal | AppMode::ShowingHelp | AppMode::ShowingStats | AppMode::ShowingCalendar | AppMode::EditingText => String::new(),
+        };
+        let takes_text = matches!(
+            state.app_mode,
+            AppMode::AddingTask
+                | AppMode::CommandMode
+                | AppMode::Searching
+                | AppMode::SettingDue
+                | AppMode::SettingEstimate
+                | AppMode::SettingReminder
+                | AppMode::Snoozing
+                | AppMode::ConfirmingClearAll
+        );
+        render_prompt(f, main_chunks[1], prompt_text, state.prompt_error, takes_text, !state.mini);
+    }
+
+    // Help overlay
+    if *state.app_mode == AppMode::ShowingHelp {
+        *state.help_height = render_help(f, main_chunks[0], state.help_scroll);
+    }
+
+    if *state.app_mode == AppMode::EditingText && let Some(editor) = state.editor.as_deref_mut() {
+        render_editor(f, main_chunks[0], editor, state.prompt_error);
+    }
+
+    if *state.app_mode == AppMode::ShowingCalendar {
+        render_calendar(f, main_chunks[0], state);
+    }
+
+    if *state.app_mode == AppMode::ShowingStats {
+        render_stats(f, main_chunks[0], state);
+    }
+
+    // Debug area at bottom (shown in debug mode when there's room)
+    if show_debug {
+        render_debug(f, main_chunks[status_index + 1], state.debug_log);
+    }
+
+    // Without colour, emphasis comes only from modifiers like bold and reverse
+    if state.no_color {
+        for cell in f.buffer_mut().content.iter_mut() {
+            cell.fg = Color::Reset;
+            cell.bg = Color::Reset;
+        }
+    }
+}
+
+/// Draws the selected task alone in the middle of `area`, for focus mode.
+fn render_focus(f: &mut Frame, area: Rect, state: &FrameState) {
+    let mut lines = Vec::new();
+    if state.has_selection {
+        let task = &state.tasks[state.selected];
+        let position = state.visible.iter().position(|&i| i == state.selected).map_or(0, |p| p + 1);
+        lines.push(Line::from(Span::styled(
+            format!("Task {} of {}", position, state.visible.len()),
+            Style::default().fg(Color::DarkGray),
+        )));
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("{} {}", state.config.checkbox(task.status()).trim_end(), task.text),
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        let mut details = Vec::new();
+        if task.time_spent_secs > 0 {
+            details.push(format!("⏱ {}", format_duration(task.time_spent_secs)));
+        }
+        if let Some(due) = task.due_label(&state.config.date_format) {
+            details.push(format!("due {}", due));
+        }
+        if !details.is_empty() {
+            lines.push(Line::from(""));
+            lines.push(Line::from(Span::styled(details.join(" · "), Style::default().fg(Color::DarkGray))));
+        }
+    } else {
+        lines.push(Line::from("Nothing to focus on — press 'f' to go back"));
+    }
+    // Pad from the top so the block sits in the middle of the border
+    let block = state.block("Focus".to_string());
+    let padding = (block.inner(area).height as usize).saturating_sub(lines.len()) / 2;
+    let mut text = vec![Line::from(""); padding];
+    text.extend(lines);
+    let paragraph = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true })
+        .block(block);
+    f.render_widget(paragraph, area);
+}
+
+/// The list's title: progress, then whatever narrows or reorders the view.
+fn list_title(state: &FrameState) -> String {
+    let tasks = state.tasks;
+    // `*` marks changes that haven't reached the disk yet
+    let unsaved = if state.dirty || state.saves_in_flight > 0 { "*" } else { "" };
+    let mut title = if tasks.is_empty() {
+        format!("TODO{} — no tasks", unsaved)
+    } else {
+        let real = tasks.iter().filter(|task| !task.separator);
+        format!("TODO{} — {}/{} done", unsaved, real.clone().filter(|task| task.done).count(), real.count())
+    };
+    if state.read_only {
+        title.push_str(" [read-only]");
+    }
+    if !state.marked.is_empty() {
+        title.push_str(&format!(" · {} marked", state.marked.len()));
+    }
+    if state.pending_block.is_some() {
+        title.push_str(" · B on its blocker");
+    }
+    if state.group_view {
+        title.push_str(" · grouped");
+    } else if state.agenda_view {
+        title.push_str(" · agenda");
+    }
+    if state.snoozed_view {
+        title.push_str(" · snoozed");
+    }
+    if let Some(key) = state.sort_view {
+        title.push_str(&format!(" · sorted by {}", key.name()));
+    }
+    if let Some(category) = state.category_filter {
+        title.push_str(&format!(" · {}", category));
+    }
+    if let Some(day) = state.day_filter {
+        title.push_str(&format!(" · due {}", state.config.date(day)));
+    }
+    if state.overdue_view {
+        title.push_str(&format!(" · overdue ({})", tasks.iter().filter(|task| is_overdue(task, state.now)).count()));
+    }
+    if !state.search_query.is_empty() {
+        title.push_str(&format!(" /{}", state.search_query));
+    }
+    // Searches, views and snoozing all narrow the list
+    if state.visible.len() != tasks.len() && !state.split_view {
+        title.push_str(&format!(" · showing {} of {}", state.visible.len(), tasks.len()));
+    }
+    if let Some((_, started)) = state.active_timer {
+        let elapsed = started.elapsed().as_secs();
+        title.push_str(&format!(" ⏱ {:02}:{:02}:{:02}", elapsed / 3600, elapsed / 60 % 60, elapsed % 60));
+    }
+    title
+}
+
+/// Draws the task list, or both panes in the split view.
+fn render_tasks(f: &mut Frame, area: Rect, state: &FrameState) {
+    // The list keeps its width on narrow terminals; the space beside it gives way
+    let content_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(30)].as_ref())
+        .split(area);
+
+    let row_context = RowContext {
+        tasks: state.tasks,
+        config: state.config,
+        selected: state.selected,
+        selected_group: state.selected_group,
+        selected_in: state.selected_in,
+        active_timer: state.active_timer,
+        no_color: state.no_color,
+        snoozed_view: state.snoozed_view,
+        show_details: state.show_details,
+        spacious: state.spacious,
+        now: state.now,
+        number_width: state.visible.len().to_string().len(),
+        marked: state.marked,
+    };
+    let title = list_title(state);
+    if state.split_view {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        let pane_rows = match state.focused_pane {
+            Pane::Inbox => [state.rows, state.other_rows],
+            Pane::Scheduled => [state.other_rows, state.rows],
+        };
+        for (index, pane) in [Pane::Inbox, Pane::Scheduled].into_iter().enumerate() {
+            let count = pane_rows[index].iter().filter(|row| matches!(row, Row::Task(..))).count();
+            let pane_title = match pane {
+                Pane::Inbox => format!("{} · Inbox ({})", title, count),
+                Pane::Scheduled => format!("Scheduled ({})", count),
+            };
+            let mut block = state.block(pane_title);
+            if pane == state.focused_pane {
+                block = block.border_style(Style::default().fg(Color::Cyan));
+            }
+            let placeholder = match pane {
+                Pane::Inbox => "Inbox empty — every task has a date",
+                Pane::Scheduled => "Nothing scheduled — '>' gives a task a date",
+            };
+            let items = list_items(pane_rows[index], block.inner(panes[index]), &row_context);
+            render_list(f, panes[index], items, block, placeholder);
+        }
+    } else {
+        let block = state.block(title);
+        let placeholder = if state.tasks.is_empty() {
+            "No tasks yet — press 'a' to add one".to_string()
+        } else if !state.search_query.is_empty() {
+            format!("No tasks match '{}' — Esc clears the search", state.search_query)
+        } else if state.overdue_view {
+            "Nothing overdue — press 'O' to show all tasks".to_string()
+        } else if state.snoozed_view {
+            "No snoozed tasks — press 'Z' to go back".to_string()
+        } else {
+            "Every task is snoozed — press 'Z' to see them".to_string()
+        };
+        let area = if state.mini { area } else { content_chunks[1] };
+        let items = list_items(state.rows, block.inner(area), &row_context);
+        render_list(f, area, items, block, &placeholder);
+    }
+}
+
+/// Draws the status bar, with contextual key hints on the right.
+fn render_status(f: &mut Frame, area: Rect, state: &FrameState) {
+    let mut status_parts = Vec::new();
+    if let Some(warning) = state.load_warning {
+        status_parts.push(format!("⚠ {}", warning));
+    }
+    if let Some(pomo) = state.pomodoro {
+        let secs = pomo.remaining.as_secs();
+        let countdown = format!("{:02}:{:02}", secs / 60, secs % 60);
+        status_parts.push(match pomo.phase {
+            PomodoroPhase::Work => {
+                let task_text = index_of(state.tasks, pomo.task_id).map_or("", |i| state.tasks[i].text.as_str());
+                let paused = if Some(pomo.task_id) == state.selected_id { "" } else { " (paused)" };
+                format!("🍅 {} {}{}", countdown, task_text, paused)
+            }
+            PomodoroPhase::Break => format!("☕ Break {}", countdown),
+        });
+    }
+    if let Some(number) = state.number_input {
+        status_parts.push(format!("Go to: {}", number));
+    }
+    if let Some(prefix) = state.typeahead {
+        status_parts.push(format!("Find: {}", prefix));
+    }
+    if state.saves_in_flight > 0 {
+        status_parts.push("Saving…".to_string());
+    }
+    if let Some(error) = state.save_error {
+        status_parts.push(format!("⚠ Save failed: {}", error));
+    }
+    if let Some(message) = state.toast {
+        status_parts.push(message.to_string());
+    }
+    let hints = if state.mini { "" } else { key_hints(state.app_mode) };
+    let status_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(hints.width() as u16 + 1)])
+        .split(area);
+    let status_style = if state.load_warning.is_some() || state.save_error.is_some() {
+        Style::default().fg(Color::Red)
+    } else {
+        Style::default()
+    };
+    f.render_widget(Paragraph::new(status_parts.join("  ")).style(status_style), status_chunks[0]);
+    f.render_widget(
+        Paragraph::new(hints).style(Style::default().fg(Color::DarkGray)).alignment(Alignment::Right),
+        status_chunks[1],
+    );
+}
+
+/// Draws the calendar overlay: open tasks due per day of the month.
+fn render_calendar(f: &mut Frame, area: Rect, state: &FrameState) {
+    let mut lines = vec![
+        Line::from(Span::styled(
+            state.calendar_day.format("%B %Y").to_string(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(Span::styled(" Mo    Tu    We    Th    Fr    Sa    Su", Style::default().fg(Color::DarkGray))),
+    ];
+    for week in month_grid(state.calendar_day) {
+        let mut spans = Vec::new();
+        for cell in week {
+            let Some(date) = cell else {
+                spans.push(Span::raw("      "));
+                continue;
+            };
+            let due = state.tasks.iter().filter(|task| !task.done && task.due == Some(date)).count();
+            let count = if due > 0 { format!("·{}", due) } else { String::new() };
+            let mut style = Style::default();
+            if due > 0 {
+                style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+            }
+            if date == state.today {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            if date == state.calendar_day {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            spans.push(Span::styled(format!("{:>3}{:<2}", date.day(), count), style));
+            spans.push(Span::raw(" "));
+        }
+        lines.push(Line::from(spans));
+    }
+    let calendar_paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Calendar"));
+    f.render_widget(Clear, area);
+    f.render_widget(calendar_paragraph, area);
+}
+
+/// Draws the statistics overlay, with a sparkline of recent completions.
+fn render_stats(f: &mut Frame, area: Rect, state: &FrameState) {
+    let mut stats_text = Stats::compute(state.tasks, state.now).lines().join("\n");
+    stats_text.push_str("\n\nPress any key to close...");
+    let stats_block = Block::default().borders(Borders::ALL).title("Statistics");
+    let stats_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(5)])
+        .split(stats_block.inner(area));
+    let completions = completions_per_day(state.tasks, state.today, SPARKLINE_DAYS);
+    let busiest = completions.iter().max().copied().unwrap_or(0);
+    let sparkline = Sparkline::default()
+        .block(Block::default().borders(Borders::TOP).title(format!(
+            "Completed per day, last {} days (most: {})",
+            SPARKLINE_DAYS, busiest
+        )))
+        .data(&completions)
+        .style(Style::default().fg(Color::Green));
+    f.render_widget(Clear, area);
+    f.render_widget(stats_block, area);
+    f.render_widget(Paragraph::new(stats_text), stats_chunks[0]);
+    f.render_widget(sparkline, stats_chunks[1]);
+}
+
+/// Normal-mode keys by section, as listed in the help overlay. Keep in sync
+/// with the key handlers below.
+const HELP_SECTIONS: &[(&str, &[(&str, &str)])] = &[
+    (
+        "Navigation",
+        &[
+            ("↑/↓", "Navigate tasks"),
+            ("0-9", "Jump to task number (Enter or pause)"),
+            ("Alt+1-9", "Toggle task number 1-9 done"),
+            ("/", "Search tasks (fuzzy)"),
+            (":", "Command mode (sort, toggle N, clear-done, clear-all, export, export-outline, s/find/replace/)"),
+            ("Space", "Toggle task completion (all marked, if any)"),
+            ("w", "Cycle todo → in progress → done"),
+            ("W", "Work on this task only (others back to todo)"),
+            ("q", "Quit application (Ctrl+c from anywhere)"),
+        ],
+    ),
+    (
+        "Task Management",
+        &[
+            ("a", "Add new task"),
+            ("A", "Add a task due today"),
+            ("p", "Paste task(s) from clipboard"),
+            ("t", "Start/stop timer on task"),
+            ("P", "Start/cancel pomodoro on task"),
+            ("m", "Mark/unmark task (Esc clears marks)"),
+            ("d", "Delete selected task, or all marked"),
+            ("u", "Undo the last delete"),
+            ("Ctrl+↑/↓", "Move task (or all marked) to the top/bottom"),
+            ("D", "Set due date"),
+            ("E", "Edit task text in a full-screen editor (Ctrl+s saves)"),
+            ("e", "Set effort estimate"),
+            ("R", "Remind every N minutes"),
+            ("B", "Make task wait on another: B here, then B on the blocker"),
+            ("x", "Snooze the reminder that just went off"),
+            ("z", "Snooze task until a date"),
+            ("c", "Cycle task category"),
+        ],
+    ),
+    (
+        "Interface",
+        &[
+            ("Ctrl+Space", "Hide/show todo list"),
+            ("Ctrl+e", "Edit todos.json in $EDITOR"),
+            ("Ctrl+s", "Save now"),
+            ("r", "Reload todos.json from disk"),
+            ("v", "Show/hide task details"),
+            ("V", "Switch between compact and spacious rows"),
+            ("g", "Toggle agenda view"),
+            ("G", "Group by #tag (Space/Enter folds)"),
+            ("i", "Inbox and scheduled side by side (Tab switches, > dates, < undates)"),
+            ("Z", "Show snoozed tasks"),
+            ("O", "Show only overdue tasks"),
+            ("b", "Show done tasks at the bottom"),
+            ("f", "Focus on the selected task"),
+            ("C", "Cycle category filter"),
+            ("M", "Calendar of due dates"),
+            ("h", "Show/hide this help"),
+            ("s", "Show statistics"),
+            ("Esc", "Close help or cancel action"),
+        ],
+    ),
+];
+
+/// The help overlay: `HELP_SECTIONS` with the keys highlighted and the
+/// descriptions lined up in one column.
+fn help_lines() -> Vec<Line<'static>> {
+    let key_width = HELP_SECTIONS
+        .iter()
+        .flat_map(|(_, keys)| keys.iter())
+        .map(|(key, _)| key.width())
+        .max()
+        .unwrap_or(0);
+    let heading = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = vec![Line::from(Span::styled("GOTTODO - Keyboard Shortcuts", heading))];
+    for (section, keys) in HELP_SECTIONS {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(format!("{}:", section), heading)));
+        for (key, description) in *keys {
+            lines.push(Line::from(vec![
+                Span::raw("  "),
+                Span::styled(
+                    format!("{}{}", key, " ".repeat(key_width - key.width())),
+                    Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!("  {}", description)),
+            ]));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("↑/↓ or PgUp/PgDn to scroll, Esc or q to close", Style::default().fg(Color::DarkGray))));
+    lines
+}
+
+/// The shortcut hint shown in the footer for each mode. Keep in sync with the
+/// key handlers below.
+fn key_hints(mode: &AppMode) -> &'static str {
+    match mode {
+        AppMode::Normal => "a add · d delete · space done · / search · : command · h help · q quit",
+        AppMode::AddingTask => "Enter save · Tab complete #tag · Esc cancel",
+        AppMode::ConfirmingDelete | AppMode::ConfirmingDeleteMarked => "y delete · n/Esc cancel",
+        AppMode::Searching => "type to filter · ↑/↓ move · Enter apply · Esc clear",
+        AppMode::CommandMode => "Enter run · Esc cancel",
+        AppMode::ConfirmingReplace => "y replace · n/Esc cancel",
+        AppMode::ConfirmingClearAll => "type yes · Enter confirm · Esc cancel",
+        AppMode::SettingDue | AppMode::SettingEstimate | AppMode::SettingReminder => "Enter set · empty clears · Esc cancel",
+        AppMode::Snoozing => "Enter snooze · empty wakes · Esc cancel",
+        AppMode::ConfirmingDuplicate => "y add anyway · n edit · Esc cancel",
+        AppMode::ShowingHelp => "↑/↓ PgUp/PgDn scroll · Esc/q close",
+        AppMode::EditingText => "Ctrl+s save · Enter new line · Esc cancel",
+        AppMode::ConfirmingQuit => "y save time and quit · n quit without it · Esc stay",
+        AppMode::ConfirmingReload => "y reload · n/Esc cancel",
+        AppMode::ShowingStats => "any key close",
+        AppMode::ShowingCalendar => "←/→/↑/↓ day · </> month · Enter filter · Backspace clear · Esc close",
+    }
+}
+
+/// Applies a typed character or Backspace to a prompt's input. Returns false
+/// for any other key so the caller can handle it.
+fn edit_input(input: &mut String, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Backspace => {
+            input.pop();
+            true
+        }
+        KeyCode::Char(c) => {
+            input.push(c);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// What `--filter` (or `GOTTODO_FILTER`) opens the list narrowed to.
+#[derive(Debug, PartialEq)]
+enum StartupFilter {
+    Category(String),
+    Search(String),
+}
+
+/// Reads a startup filter: `#name` is a category when one is called that,
+/// otherwise a search for the `#name` tag; anything else is a search. A
+/// `#name` that is neither gives the warning to show instead.
+fn startup_filter(filter: &str, categories: &[String], tags: &[String]) -> Result<StartupFilter, String> {
+    match filter.strip_prefix('#') {
+        Some(name) if categories.iter().any(|known| known == name) => Ok(StartupFilter::Category(name.to_string())),
+        Some(_) if tags.iter().any(|tag| tag.eq_ignore_ascii_case(filter)) => Ok(StartupFilter::Search(filter.to_string())),
+        Some(name) => Err(format!("No category or tag '{}' to filter on; showing everything", name)),
+        None => Ok(StartupFilter::Search(filter.to_string())),
+    }
+}
+
+/// A status bar message paired with the time it disappears.
+fn toast(message: impl Into<String>) -> (String, Instant) {
+    (message.into(), Instant::now() + TOAST_DURATION)
+}
+
+/// Cuts `text` to at most `max_width` terminal columns, ending in `…` when
+/// shortened. Wide glyphs (CJK, emoji) count as two columns and are never split.
+fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if width + char_width + 1 > max_width {
+            break;
+        }
+        width += char_width;
+        truncated.push(c);
+    }
+    if max_width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+/// Splits `text` into spans cut to `width` columns, styling the chars at
+/// `positions` as matches and, with `markup`, emphasis from `parse_markup`.
+/// Matches are underlined as well as coloured so they still stand out on the
+/// selected row's background. Spans only add to the row's own style.
+fn highlight_spans(text: &str, positions: &[usize], width: usize, markup: bool) -> Vec<Span<'static>> {
+    let match_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let chars: Vec<char> = text.chars().collect();
+    let shown = if markup { parse_markup(text) } else { (0..chars.len()).map(|i| (i, Emphasis::Plain)).collect() };
+    let shown_text: String = shown.iter().map(|&(i, _)| chars[i]).collect();
+    let cut = truncate_to_width(&shown_text, width);
+    // Chars of `cut` that came from `text`; past them is the `…`, if any
+    let kept = if cut == shown_text { shown.len() } else { cut.chars().count().saturating_sub(1) };
+    let mut spans: Vec<Span<'static>> = Vec::new();
+    let mut run = String::new();
+    let mut run_style = Style::default();
+    for (k, c) in cut.chars().enumerate() {
+        let source = shown.get(k).filter(|_| k < kept);
+        let mut style = match source {
+            Some(&(_, Emphasis::Bold)) => Style::default().add_modifier(Modifier::BOLD),
+            Some(&(_, Emphasis::Italic)) => Style::default().add_modifier(Modifier::ITALIC),
+            Some(&(_, Emphasis::Code)) => Style::default().fg(Color::Cyan),
+            _ => Style::default(),
+        };
+        if source.is_some_and(|(i, _)| positions.contains(i)) {
+            style = style.patch(match_style);
+        }
+        if style != run_style && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+        }
+        run_style = style;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, run_style));
+    }
+    spans
+}
+
+/// Stops the running timer, if any, crediting the elapsed seconds to its task.
+/// Returns the task id and the seconds added.
+fn stop_timer(tasks: &mut [Task], timer: &mut Option<(u64, Instant)>) -> Option<(u64, u64)> {
+    let (id, started) = timer.take()?;
+    let elapsed = started.elapsed().as_secs();
+    let task = tasks.iter_mut().find(|task| task.id == id)?;
+    task.time_spent_secs += elapsed;
+    Some((id, elapsed))
+}
+
+/// Whether quitting asks first: the `t` timer or a pomodoro is running and
+/// `confirm_quit_with_timer` is on.
+fn quit_needs_confirm(config: &Config, active_timer: Option<(u64, Instant)>, pomodoro: Option<&Pomodoro>) -> bool {
+    config.confirm_quit_with_timer && (active_timer.is_some() || pomodoro.is_some())
+}
+
+/// Why `strict_blocking` won't let `task` be done yet.
+fn blocked_message(tasks: &[Task], task: &Task) -> String {
+    match open_blocker(tasks, task) {
+        Some(blocker) => format!("Blocked by '{}'", blocker.text),
+        None => "Not blocked".to_string(),
+    }
+}
+
+/// Deletes every marked task and clears the marks, moving the selection off
+/// anything removed. Returns the deleted tasks with their old positions for undo.
+fn delete_marked(tasks: &mut Vec<Task>, selected: &mut usize, marked: &mut HashSet<u64>) -> Vec<(usize, Task)> {
+    let deleted = tasks.iter().cloned().enumerate().filter(|(_, task)| marked.contains(&task.id)).collect();
+    let remap = delete_tasks(tasks, marked);
+    *selected = remap_selection(&remap, *selected);
+    marked.clear();
+    deleted
+}
+
+/// Deletes the task awaiting confirmation, wherever it has moved to, and
+/// clears `pending`. Returns it with its old position for undo, or `None`
+/// when it is gone (say, the list was reloaded while the prompt was up).
+fn delete_pending(tasks: &mut Vec<Task>, selected: &mut usize, pending: &mut Option<u64>) -> Option<(usize, Task)> {
+    let index = pending.take().and_then(|id| index_of(tasks, id))?;
+    let task = tasks[index].clone();
+    let remap = delete_task(tasks, index);
+    *selected = remap_selection(&remap, index);
+    Some((index, task))
+}
+
+/// A copy of the list to save, with the last id handed out.
+type Snapshot = (Vec<Task>, u64);
+
+/// Starts the thread saves run on, so slow storage can't freeze the UI. Each
+/// snapshot sent gets exactly one result back.
+fn spawn_saver(
+    mut store: impl TaskStore + Send + 'static,
+) -> (mpsc::Sender<Snapshot>, mpsc::Receiver<std::io::Result<()>>, thread::JoinHandle<()>) {
+    let (save_tx, save_rx) = mpsc::channel::<Snapshot>();
+    let (result_tx, result_rx) = mpsc::channel();
+    let saver = thread::spawn(move || {
+        for (tasks, last_id) in save_rx {
+            let _ = result_tx.send(store.save(&tasks, last_id));
+        }
+    });
+    (save_tx, result_rx, saver)
+}
+
+/// Queues `last` (the changes the debounce hadn't sent yet) and waits for
+/// every queued save to reach the disk. Returns the errors of any that failed.
+fn finish_saves(
+    save_tx: mpsc::Sender<Snapshot>,
+    saver: thread::JoinHandle<()>,
+    results: &mpsc::Receiver<std::io::Result<()>>,
+    last: Option<Snapshot>,
+) -> Vec<String> {
+    if let Some(snapshot) = last {
+        let _ = save_tx.send(snapshot);
+    }
+    drop(save_tx);
+    let _ = saver.join();
+    results.try_iter().filter_map(Result::err).map(|err| err.to_string()).collect()
+}
+
+/// Replaces the list with what `todos.json` holds now, keeping the selection
+/// on the same task if it is still there. `last_id` takes in ids another
+/// instance has handed out. Returns whether ids had to be assigned, which
+/// needs a save.
+fn reload_tasks(tasks: &mut Vec<Task>, last_id: &mut u64, selected: &mut usize, load_warning: &mut Option<String>) -> bool {
+    let selected_id = tasks.get(*selected).map(|task| task.id);
+    let store = FileStore::new("todos.json");
+    let loaded = store.load();
+    *tasks = loaded.tasks;
+    *load_warning = loaded.warning;
+    *last_id = (*last_id).max(store.last_id());
+    let assigned = assign_missing_ids(tasks, last_id);
+    *selected = selected_id
+        .and_then(|id| index_of(tasks, id))
+        .unwrap_or_else(|| (*selected).min(tasks.len().saturating_sub(1)));
+    assigned
+}
+
+/// Advances the pomodoro countdown by the time since the last tick. The work
+/// phase only counts down while its task is selected. Returns the phase that
+/// just finished, moving from work to break or ending after the break.
+fn tick_pomodoro(pomodoro: &mut Option<Pomodoro>, selected_id: Option<u64>, break_length: Duration) -> Option<PomodoroPhase> {
+    let pomo = pomodoro.as_mut()?;
+    let now = Instant::now();
+    if pomo.phase == PomodoroPhase::Break || Some(pomo.task_id) == selected_id {
+        pomo.remaining = pomo.remaining.saturating_sub(now - pomo.last_tick);
+    }
+    pomo.last_tick = now;
+    if !pomo.remaining.is_zero() {
+        return None;
+    }
+    let finished = pomo.phase;
+    match finished {
+        PomodoroPhase::Work => {
+            pomo.phase = PomodoroPhase::Break;
+            pomo.remaining = break_length;
+        }
+        PomodoroPhase::Break => *pomodoro = None,
+    }
+    Some(finished)
+}
+
+/// Fires a best-effort desktop notification; failures are ignored.
+fn notify(message: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = process::Command::new("osascript");
+        command.args(["-e", &format!("display notification {:?} with title \"gottodo\"", message)]);
+        command
+    } else {
+        let mut command = process::Command::new("notify-send");
+        command.args(["gottodo", message]);
+        command
+    };
+    let _ = command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+}
+
+/// Reads the system clipboard through whichever platform tool is available.
+/// Returns `None` when no tool works or the contents aren't valid text.
+fn read_clipboard() -> Option<String> {
+    let candidates: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbpaste", &[])]
+    } else if cfg!(windows) {
+        &[("powershell", &["-NoProfile", "-Command", "Get-Clipboard"])]
+    } else {
+        &[
+            ("wl-paste", &["--no-newline"]),
+            ("xclip", &["-selection", "clipboard", "-o"]),
+            ("xsel", &["--clipboard", "--output"]),
+        ]
+    };
+    candidates.iter().find_map(|(program, args)| {
+        let output = process::Command::new(program)
+            .args(*args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    })
+}
+
+/// Runs `$EDITOR` (or `vi`) on `path` and waits for it to exit. The editor
+/// command may carry its own arguments, e.g. `code --wait`.
+fn run_editor(path: &str) -> std::io::Result<()> {
+    let editor = env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = process::Command::new(program).args(words).arg(path).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!("{} exited with {}", program, status)))
+    }
+}
+
+/// Puts the terminal back the way the shell left it.
+fn restore_terminal() -> std::io::Result<()> {
+    terminal::disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen)
+}
+
+/// Adds the tasks in a plain text file to `todos.json` and exits, without
+/// starting the TUI. Merges by default, skipping texts already in the list;
+/// `replace` discards the existing list instead. With `outline` the file is
+/// read by `parse_outline`, so indented lines land under headings.
+fn import_txt(path: &str, replace: bool, outline: bool) -> ! {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("gottodo: can't read {}: {}", path, err);
+            process::exit(1);
+        }
+    };
+    let mut store = FileStore::new("todos.json");
+    let mut tasks = if replace {
+        Vec::new()
+    } else {
+        let loaded = store.load();
+        if let Some(warning) = loaded.warning {
+            eprintln!("gottodo: {}", warning);
+        }
+        loaded.tasks
+    };
+    // Even a replaced list's ids stay used
+    let mut last_id = store.last_id();
+    assign_missing_ids(&mut tasks, &mut last_id);
+    let (added, skipped) = if outline {
+        merge_outline(&mut tasks, &mut last_id, &parse_outline(&contents))
+    } else {
+        merge_tasks(&mut tasks, &mut last_id, &parse_txt_tasks(&contents))
+    };
+    if let Err(err) = store.save(&tasks, last_id) {
+        eprintln!("gottodo: can't save todos.json: {}", err);
+        process::exit(1);
+    }
+    println!("Imported {} task(s) from {}, skipped {} duplicate(s)", added, path, skipped);
+    process::exit(0);
+}
+
+/// The tasks at `path` for a mode that only reports on them. Nothing is
+/// written: a missing file stays missing and a corrupt one isn't backed up
+/// or rewritten. A load warning goes to stderr, away from the report.
+fn report_tasks(path: &Path) -> Vec<Task> {
+    let loaded = FileStore::new(path).read();
+    if let Some(warning) = loaded.warning {
+        eprintln!("gottodo: {}", warning);
+    }
+    loaded.tasks
+}
+
+/// Rebuilds `todos.json` from `events.jsonl` and exits, keeping the file it
+/// replaces as `todos.json.bak`.
+fn replay_event_log() -> ! {
+    let events = match read_events(Path::new("events.jsonl")) {
+        Ok(events) => events,
+        Err(err) => {
+            eprintln!("gottodo: can't read events.jsonl: {}", err);
+            process::exit(1);
+        }
+    };
+    let count = events.len();
+    let mut store = FileStore::new("todos.json");
+    // Tasks the log saw deleted keep their ids too
+    let logged = events.iter().filter_map(|event| match event {
+        TaskEvent::Add { task } | TaskEvent::Update { task } | TaskEvent::Delete { task } => Some(task.id),
+        TaskEvent::Reorder { .. } => None,
+    });
+    let last_id = logged.max().unwrap_or(0).max(store.last_id());
+    let tasks = replay_events(events);
+    if Path::new("todos.json").exists()
+        && let Err(err) = std::fs::copy("todos.json", "todos.json.bak")
+    {
+        eprintln!("gottodo: can't back up todos.json: {}", err);
+        process::exit(1);
+    }
+    if let Err(err) = store.save(&tasks, last_id) {
+        eprintln!("gottodo: can't save todos.json: {}", err);
+        process::exit(1);
+    }
+    println!("Rebuilt todos.json with {} task(s) from {} event(s); the previous file is in todos.json.bak", tasks.len(), count);
+    process::exit(0);
+}
+
+/// Command-line options. Without a subcommand the list opens in the
+/// terminal; the subcommands do one job and exit. Each subcommand is also
+/// accepted as the hidden flag it used to be (`--stats`, `--import-txt PATH`
+/// and so on), so existing scripts keep working.
+#[derive(Parser)]
+#[command(version, about = "A keyboard-driven todo list for the terminal, kept in ./todos.json")]
+#[command(args_conflicts_with_subcommands = true, group(clap::ArgGroup::new("legacy_mode").multiple(false)))]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Mode>,
+    /// Read settings from this file instead of the usual places
+    #[arg(long, value_name = "PATH", global = true)]
+    config: Option<String>,
+    /// Browse the list without changing or saving it
+    #[arg(long)]
+    read_only: bool,
+    /// Show the debug log pane
+    #[arg(long)]
+    debug: bool,
+    /// Accept commands on a Unix socket, gottodo.sock unless a path is given
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "gottodo.sock")]
+    listen: Option<PathBuf>,
+    /// Bare list for a small pane such as a tmux sidebar: no borders, key hints or debug pane
+    #[arg(long)]
+    mini: bool,
+    /// Start with this filter applied: `#name` for a category or tag, other text searches (or set GOTTODO_FILTER)
+    #[arg(long, value_name = "FILTER")]
+    filter: Option<String>,
+    /// Serve the list read-only over HTTP on localhost: JSON at /tasks, a page at /
+    #[arg(long, value_name = "PORT")]
+    serve: Option<u16>,
+    /// Open straight into the add-task prompt
+    #[arg(long, conflicts_with = "read_only")]
+    add: bool,
+    #[arg(long, hide = true, group = "legacy_mode")]
+    stats: bool,
+    #[arg(long, hide = true, group = "legacy_mode")]
+    status: bool,
+    #[arg(long, hide = true, group = "legacy_mode")]
+    journal: bool,
+    #[arg(long, hide = true, requires = "journal")]
+    date: Option<NaiveDate>,
+    #[arg(long, hide = true, group = "legacy_mode")]
+    export_ics: Option<String>,
+    #[arg(long, hide = true, group = "legacy_mode")]
+    import_txt: Option<String>,
+    #[arg(long, hide = true, requires = "import_txt", conflicts_with = "replace")]
+    merge: bool,
+    #[arg(long, hide = true, requires = "import_txt")]
+    replace: bool,
+    #[arg(long, hide = true, requires = "import_txt")]
+    outline: bool,
+    #[arg(long, hide = true, group = "legacy_mode")]
+    replay_events: bool,
+    #[arg(long, hide = true, group = "legacy_mode")]
+    init: bool,
+    #[arg(long, hide = true, requires = "init")]
+    force: bool,
+}
+
+impl Cli {
+    /// The subcommand given, or the one named by its old flag.
+    fn mode(&self) -> Option<Mode> {
+        if let Some(mode) = &self.command {
+            return Some(mode.clone());
+        }
+        if self.stats {
+            Some(Mode::Stats)
+        } else if self.status {
+            Some(Mode::Status)
+        } else if self.journal {
+            Some(Mode::Journal { date: self.date })
+        } else if let Some(path) = &self.export_ics {
+            Some(Mode::ExportIcs { path: path.clone() })
+        } else if let Some(path) = &self.import_txt {
+            Some(Mode::ImportTxt { path: path.clone(), merge: self.merge, replace: self.replace, outline: self.outline })
+        } else if self.replay_events {
+            Some(Mode::ReplayEvents)
+        } else if self.init {
+            Some(Mode::Init { force: self.force })
+        } else {
+            None
+        }
+    }
+}
+
+/// The modes that run without opening the list.
+#[derive(Subcommand, Debug, PartialEq, Clone)]
+enum Mode {
+    /// Print statistics
+    Stats,
+    /// Print how many tasks are pending; exits with 1 if any are, 0 if none
+    Status,
+    /// Print the tasks completed today
+    Journal {
+        /// The day to list instead of today
+        #[arg(long, value_name = "YYYY-MM-DD")]
+        date: Option<NaiveDate>,
+    },
+    /// Write the tasks that have a due date to an iCalendar file
+    ExportIcs {
+        /// The .ics file to write
+        #[arg(value_name = "PATH")]
+        path: String,
+    },
+    /// Add each line of a text file as a task
+    ImportTxt {
+        /// The text file to read, one task per line
+        #[arg(value_name = "PATH")]
+        path: String,
+        /// Skip lines already in the list (the default)
+        #[arg(long, conflicts_with = "replace")]
+        merge: bool,
+        /// Replace the list instead of merging
+        #[arg(long)]
+        replace: bool,
+        /// Read the file as an indented outline
+        #[arg(long)]
+        outline: bool,
+    },
+    /// Rebuild todos.json from events.jsonl
+    ReplayEvents,
+    /// Create todos.json and a commented config.toml where they are looked for
+    Init {
+        /// Overwrite an existing config.toml
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// A line from a control socket client and where its reply goes.
+type RemoteRequest = (String, mpsc::Sender<String>);
+
+/// Binds the `--listen` socket, clearing away a file left by an instance that
+/// didn't exit cleanly but refusing to take over one that is still running.
+#[cfg(unix)]
+fn bind_socket(path: &Path) -> Result<UnixListener, String> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("{} is in use by another gottodo", path.display()));
+        }
+        let _ = std::fs::remove_file(path);
+    }
+    UnixListener::bind(path).map_err(|err| format!("can't listen on {}: {}", path.display(), err))
+}
+
+/// Hands each line from each client to the main loop and writes back its
+/// reply. Every connection gets its own thread so an idle client can't hold
+/// up the others.
+#[cfg(unix)]
+fn serve_socket(listener: UnixListener, requests: mpsc::Sender<RemoteRequest>) {
+    for stream in listener.incoming().flatten() {
+        let requests = requests.clone();
+        thread::spawn(move || {
+            let Ok(reader) = stream.try_clone() else { return };
+            let mut writer = stream;
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else { break };
+                let (reply_tx, reply_rx) = mpsc::channel();
+                if requests.send((line, reply_tx)).is_err() {
+                    break;
+                }
+                let Ok(reply) = reply_rx.recv() else { break };
+                if writeln!(writer, "{}", reply).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Answers each `--serve` request from todos.json as it is on disk, so the
+/// page shows what was last saved. The file is only read, never created or
+/// backed up, and saves replace it whole, so a request can't see half a
+/// save. Only GET is accepted; the list can't be changed over HTTP.
+fn serve_http(listener: TcpListener) {
+    for stream in listener.incoming().flatten() {
+        thread::spawn(move || {
+            // A client that never finishes its request can't keep the thread forever
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+            let Ok(reader) = stream.try_clone() else { return };
+            let mut lines = BufReader::new(reader).lines();
+            let Some(Ok(request)) = lines.next() else { return };
+            // The headers are read and ignored so the client sees its request consumed
+            for line in lines.by_ref() {
+                if line.map_or(true, |line| line.is_empty()) {
+                    break;
+                }
+            }
+            let mut parts = request.split_whitespace();
+            let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+            let (status, content_type, body) = if method != "GET" {
+                ("405 Method Not Allowed", "text/plain", "Only GET is supported\n".to_string())
+            } else {
+                match path.split('?').next().unwrap_or("") {
+                    "/" => ("200 OK", "text/html; charset=utf-8", tasks_html(&FileStore::new("todos.json").read().tasks)),
+                    "/tasks" => {
+                        let tasks = FileStore::new("todos.json").read().tasks;
+                        ("200 OK", "application/json", serde_json::to_string_pretty(&tasks).unwrap_or_default())
+                    }
+                    _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+                }
+            };
+            let mut writer = stream;
+            let _ = write!(
+                writer,
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                content_type,
+                body.len(),
+                body
+            );
+        });
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+    let debug_mode = cli.debug;
+    // Honour https://no-color.org and terminals that can't draw colour
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+        || env::var("TERM").is_ok_and(|term| term == "dumb");
+    // Browse without any way to change or save the list
+    let read_only = cli.read_only;
+    let mini = cli.mini;
+    match &cli.mode() {
+        Some(Mode::ReplayEvents) => replay_event_log(),
+        Some(Mode::Stats) => {
+            for line in Stats::compute(&report_tasks(Path::new("todos.json")), Local::now()).lines() {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        Some(Mode::Init { force }) => init_files(&process_config_path(cli.config.as_deref()).0, *force),
+        Some(Mode::Status) => {
+            let tasks = report_tasks(Path::new("todos.json"));
+            let pending = tasks.iter().filter(|task| !task.done && !task.separator).count();
+            println!("{} pending task(s)", pending);
+            process::exit(if pending == 0 { 0 } else { 1 });
+        }
+        Some(Mode::Journal { date }) => {
+            let tasks = report_tasks(Path::new("todos.json"));
+            print!("{}", journal(&tasks, date.unwrap_or_else(|| Local::now().date_naive())));
+            return Ok(());
+        }
+        Some(Mode::ExportIcs { path }) => {
+            let tasks = report_tasks(Path::new("todos.json"));
+            if let Err(err) = std::fs::write(path, export_ics(&tasks, Local::now())) {
+                eprintln!("gottodo: can't write {}: {}", path, err);
+                process::exit(1);
+            }
+            let dated = tasks.iter().filter(|task| task.due.is_some() && !task.separator).count();
+            println!("Exported {} dated task(s) to {}", dated, path);
+            return Ok(());
+        }
+        Some(Mode::ImportTxt { path, replace, outline, .. }) => import_txt(path, *replace, *outline),
+        None => {}
+    }
+    // Better to stop here than open a list that can never be saved
+    if let Err(err) = FileStore::new("todos.json").check() {
+        eprintln!("gottodo: {}", err);
+        process::exit(1);
+    }
+    // Scripts drive the running app through this socket
+    let listen_path = cli.listen;
+    #[cfg(not(unix))]
+    if listen_path.is_some() {
+        eprintln!("gottodo: --listen needs Unix domain sockets");
+        process::exit(2);
+    }
+
+    // Bound before the terminal is taken over so a busy port is reported plainly;
+    // the server thread goes away with the process
+    if let Some(port) = cli.serve {
+        match TcpListener::bind(("127.0.0.1", port)) {
+            Ok(listener) => {
+                thread::spawn(move || serve_http(listener));
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AddrInUse => {
+                eprintln!("gottodo: port {} is already in use; pick another with --serve <port>", port);
+                process::exit(1);
+            }
+            Err(err) => {
+                eprintln!("gottodo: can't serve on port {}: {}", port, err);
+                process::exit(1);
+            }
+        }
+    }
+
+    let mut stdout = stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen)?;
+    // Restore the terminal before the panic message prints so it lands in the scrollback
+    let default_hook = panic::take_hook();
+    let socket_file = listen_path.clone();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        if let Some(path) = &socket_file {
+            let _ = std::fs::remove_file(path);
+        }
+        default_hook(info);
+    }));
+    let (remote_tx, remote_rx) = mpsc::channel::<RemoteRequest>();
+    #[cfg(unix)]
+    if let Some(path) = &listen_path {
+        match bind_socket(path) {
+            Ok(listener) => {
+                thread::spawn(move || serve_socket(listener, remote_tx));
+            }
+            Err(err) => {
+                restore_terminal()?;
+                eprintln!("gottodo: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let (config_file, explicit_config) = process_config_path(cli.config.as_deref());
+    let (config, config_warning) = load_config(&config_file, explicit_config);
+    let mut store = FileStore::new("todos.json");
+    let loaded = store.load();
+    let mut tasks = loaded.tasks;
+    // A corrupt data file or broken config is reported until the next keypress
+    let mut load_warning = match (loaded.warning, config_warning) {
+        (Some(data), Some(config)) => Some(format!("{}; {}", data, config)),
+        (data, config) => data.or(config),
+    };
+    let mut last_id = store.last_id();
+    let mut needs_save = assign_missing_ids(&mut tasks, &mut last_id);
+    let mut purged = 0;
+    if let Some(days) = config.auto_purge_days.filter(|_| !read_only) {
+        let now = Local::now();
+        let before = tasks.len();
+        tasks.retain(|task| !should_purge(task, now, days));
+        purged = before - tasks.len();
+        needs_save |= purged > 0;
+    }
+    let mut ui_state = load_ui_state();
+    let reset_period = parse_reset_period(&config.reset_period);
+    let mut reset = 0;
+    if let Some(period) = reset_period.filter(|_| !read_only) {
+        let today = Local::now().date_naive();
+        // A list without a reset date starts counting from today rather than resetting
+        if ui_state.last_reset.is_none_or(|last| reset_due(last, today, period)) {
+            if ui_state.last_reset.is_some() {
+                for task in tasks.iter_mut().filter(|task| task.done) {
+                    task.done = false;
+                    task.completed_at = None;
+                    reset += 1;
+                }
+                needs_save |= reset > 0;
+            }
+            ui_state.last_reset = Some(today);
+            save_ui_state(&ui_state);
+        }
+    }
+    // A failed save is shown like any other and retried once the loop runs
+    let mut startup_save_error = None;
+    if needs_save && !read_only && let Err(err) = store.save(&tasks, last_id) {
+        startup_save_error = Some(err.to_string());
+    }
+    let mut saved_prefs = load_prefs();
+    // Sorting only changes what's shown; the list keeps the order it was arranged in
+    let default_sort = parse_sort_key(&config.default_sort);
+    let mut sort_view = initial_sort(saved_prefs.sort_view.as_deref(), &config.default_sort);
+    let mut selected = restore_selection(&tasks, &ui_state);
+    let mut ui_visible = true;
+    let mut debug_log: Vec<String> = Vec::new();
+    // `--add` is for quick capture, e.g. from a global hotkey
+    let mut app_mode = if cli.add { AppMode::AddingTask } else { AppMode::Normal };
+    let mut input_text = String::new();
+    let mut active_timer: Option<(u64, Instant)> = None;
+    let mut pomodoro: Option<Pomodoro> = None;
+    // When each task's reminder goes off next; kept for the session only
+    let mut reminders: HashMap<u64, DateTime<Local>> = HashMap::new();
+    // The task whose reminder went off last, for `x` to snooze
+    let mut last_reminder: Option<u64> = None;
+    let mut show_details = saved_prefs.show_details;
+    let default_spacious = config.density == "spacious";
+    let mut spacious = saved_prefs.spacious.unwrap_or(default_spacious);
+    let mut search_query = String::new();
+    // Set when the query changes, so the next frame selects its best listed match
+    let mut jump_to_best = false;
+    let mut prompt_error: Option<String> = None;
+    let mut toast_message: Option<(String, Instant)> = None;
+    if reset > 0 {
+        toast_message = Some(toast(format!("New {} — unchecked {} task(s)", if reset_period == Some(ResetPeriod::Daily) { "day" } else { "week" }, reset)));
+    }
+    // Id of the task awaiting delete confirmation
+    let mut pending_delete: Option<u64> = None;
+    // The last deleted task(s) and where they were, so `u` can put them back
+    let mut last_deleted: Vec<(usize, Task)> = Vec::new();
+    // Ids of tasks marked with `m` for a bulk action; only kept for the session
+    let mut marked: HashSet<u64> = HashSet::new();
+    // Task picked with `B`, waiting for the task that blocks it to be picked
+    let mut pending_block: Option<u64> = None;
+    // Replacement awaiting confirmation, with how many tasks it will change
+    let mut pending_replace: Option<(Replacement, usize)> = None;
+    // Task number being typed in Normal mode and when its last digit came in
+    let mut number_input: Option<(usize, Instant)> = None;
+    // Letters typed so far to jump to a task by its start, and when the last came in
+    let mut typeahead: Option<(String, Instant)> = None;
+    // What the `#tag` being completed in the add prompt was typed as, and which match is shown
+    let mut tag_completion: Option<(String, usize)> = None;
+    // Task being edited by a prompt such as the due-date one
+    let mut edit_target: Option<u64> = None;
+    // The add prompt was opened with `A`, so the new task is due today
+    let mut add_due_today = false;
+    // Restored filters that no longer match anything are dropped rather than
+    // leaving an empty list with no obvious cause
+    let known_categories = category_names(config.categories.keys(), &tasks);
+    let mut agenda_view = saved_prefs.agenda_view && !saved_prefs.group_view && !saved_prefs.split_view;
+    // Undated tasks (the inbox) and dated ones side by side, and which side has the cursor
+    let mut split_view = saved_prefs.split_view && !saved_prefs.group_view;
+    let mut focused_pane = saved_prefs.focused_pane;
+    // Tasks listed under collapsible tag headings, which groups are
+    // collapsed, the heading under the cursor (when it isn't on a task), and
+    // the group whose row of the selected task it is on, as a task with
+    // several tags is listed under each
+    let mut group_view = saved_prefs.group_view;
+    let known_tags = all_tags(&tasks);
+    let mut collapsed_groups: BTreeSet<String> = saved_prefs
+        .collapsed_groups
+        .iter()
+        .filter(|name| known_tags.contains(name) || name.as_str() == UNTAGGED)
+        .cloned()
+        .collect();
+    let mut selected_group: Option<String> = None;
+    let mut selected_in: Option<String> = None;
+    // Show only snoozed tasks instead of hiding them
+    let mut snoozed_view = saved_prefs.snoozed_view;
+    // Show only open tasks past their due date
+    let mut overdue_view = saved_prefs.overdue_view;
+    // Draw open tasks above done ones without touching the stored order
+    let mut done_last_view = saved_prefs.done_last_view;
+    // Show only the selected task, large and centred
+    let mut focus_mode = saved_prefs.focus_mode;
+    // Only show tasks in this category
+    let mut category_filter = saved_prefs.category_filter.clone().filter(|name| known_categories.contains(name));
+    // Launchers can open the list on one context
+    let initial_filter = cli.filter.clone().or_else(|| env::var("GOTTODO_FILTER").ok());
+    if let Some(filter) = initial_filter.as_deref().map(str::trim).filter(|filter| !filter.is_empty()) {
+        match startup_filter(filter, &known_categories, &known_tags) {
+            Ok(StartupFilter::Category(name)) => category_filter = Some(name),
+            Ok(StartupFilter::Search(query)) => search_query = query,
+            Err(warning) => {
+                load_warning = Some(match load_warning.take() {
+                    Some(earlier) => format!("{}; {}", earlier, warning),
+                    None => warning,
+                });
+            }
+        }
+    }
+    // Day highlighted in the calendar, and the due date the list is narrowed to
+    let mut day_filter = saved_prefs.day_filter.filter(|day| tasks.iter().any(|task| task.due == Some(*day)));
+    // First help line shown, and how many fit, as of the last frame
+    let mut help_scroll: u16 = 0;
+    let mut help_height: u16 = 0;
+    // Text being edited with `E`; the task is `edit_target`
+    let mut editor: Option<TextArea> = None;
+    let mut calendar_day = day_filter.unwrap_or_else(|| Local::now().date_naive());
+    // Mutations set `dirty`; the loop flushes at most once per SAVE_DEBOUNCE
+    let mut dirty = startup_save_error.is_some();
+    let mut last_save = Instant::now();
+    let mut event_log = None;
+    if config.event_log && !read_only {
+        match EventLog::open(Path::new("events.jsonl")) {
+            Ok(log) => event_log = Some(log),
+            Err(err) => toast_message = Some(toast(format!("Event log unavailable: {}", err))),
+        }
+    }
+    let (save_tx, result_rx, saver) = spawn_saver(store);
+    let mut saves_in_flight = 0;
+    // Shown until a later save succeeds
+    let mut save_error: Option<String> = startup_save_error;
+    let terminate = Arc::new(AtomicBool::new(false));
+    for signal in signal_hook::consts::TERM_SIGNALS {
+        signal_hook::flag::register(*signal, Arc::clone(&terminate))?;
+    }
+    
+    if debug_mode {
+        debug_log.push("Debug mode enabled".to_string());
+        debug_log.push(format!("UI visible: {}", ui_visible));
+        if default_sort.is_none() && config.default_sort != "none" {
+            debug_log.push(format!("Unknown default_sort '{}', keeping file order", config.default_sort));
+        }
+        if config.auto_purge_days.is_some() {
+            debug_log.push(format!("Purged {} old completed task(s)", purged));
+        }
+        if !matches!(config.density.as_str(), "compact" | "spacious") {
+            debug_log.push(format!("Unknown density '{}', using compact", config.density));
+        }
+        if !matches!(config.long_task_text.as_str(), "reject" | "truncate") {
+            debug_log.push(format!("Unknown long_task_text '{}', rejecting long tasks", config.long_task_text));
+        }
+        if reset_period.is_none() && config.reset_period != "none" {
+            debug_log.push(format!("Unknown reset_period '{}', not resetting", config.reset_period));
+        }
+    }
+
+    // Run the loop under catch_unwind so unsaved changes are flushed even if it panics
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| -> std::io::Result<()> {
+        loop {
+            if terminate.load(Ordering::Relaxed) {
+                if debug_mode {
+                    debug_log.push("Termination signal received".to_string());
+                }
+                break;
+            }
+            // Logs whatever any handler changed since the last pass
+            if let Some(log) = event_log.as_mut()
+                && let Err(err) = log.record(&tasks)
+            {
+                toast_message = Some(toast(format!("Event log disabled: {}", err)));
+                event_log = None;
+            }
+            // Held while asking to reload, so those changes can still be thrown away
+            let hold_save = app_mode == AppMode::ConfirmingReload;
+            if dirty && !read_only && !hold_save && last_save.elapsed() >= SAVE_DEBOUNCE && save_tx.send((tasks.clone(), last_id)).is_ok() {
+                saves_in_flight += 1;
+                dirty = false;
+                last_save = Instant::now();
+            }
+            for result in result_rx.try_iter() {
+                saves_in_flight -= 1;
+                match result {
+                    Ok(()) => {
+                        save_error = None;
+                        // Don't cover up the toast of the action that caused the save
+                        if toast_message.is_none() {
+                            toast_message = Some(toast("Saved"));
+                        }
+                    }
+                    Err(err) => {
+                        if debug_mode {
+                            debug_log.push(format!("Save failed: {}", err));
+                        }
+                        save_error = Some(err.to_string());
+                    }
+                }
+            }
+            // Requests from `--listen` clients, answered before this frame is drawn
+            for (line, reply) in remote_rx.try_iter() {
+                let answer = match parse_remote_command(&line) {
+                    Ok(RemoteCommand::List) => {
+                        let mut listing: String = tasks
+                            .iter()
+                            .map(|task| {
+                                let marker = match (task.separator, task.status()) {
+                                    (true, _) => "--",
+                                    (false, Status::Done) => "[x]",
+                                    (false, Status::InProgress) => "[~]",
+                                    (false, Status::Todo) => "[ ]",
+                                };
+                                format!("{} {} {}\n", task.id, marker, task.text)
+                            })
+                            .collect();
+                        listing.push_str("ok");
+                        listing
+                    }
+                    Ok(_) if read_only => "error: read-only mode".to_string(),
+                    Ok(RemoteCommand::Add(text)) => match config.fit_task_text(&text) {
+                        Ok((text, _)) => {
+                            let id = add_task(&mut tasks, &mut last_id, &text);
+                            dirty = true;
+                            format!("ok {}", id)
+                        }
+                        Err(err) => format!("error: {}", err.to_lowercase()),
+                    },
+                    Ok(RemoteCommand::Toggle(id)) => match index_of(&tasks, id) {
+                        Some(i) if tasks[i].separator => format!("error: {} is a separator", id),
+                        Some(i) => {
+                            tasks[i].toggle_done(Local::now());
+                            dirty = true;
+                            format!("ok {}", if tasks[i].done { "done" } else { "open" })
+                        }
+                        None => format!("error: no task {}", id),
+                    },
+                    Err(err) => format!("error: {}", err),
+                };
+                if debug_mode {
+                    debug_log.push(format!("Socket: {} -> {}", line, answer.lines().last().unwrap_or("")));
+                }
+                let _ = reply.send(answer);
+            }
+            // Handlers just flip the toggles; write them out whenever one changed
+            let prefs = Prefs {
+                show_details,
+                agenda_view,
+                done_last_view,
+                group_view,
+                collapsed_groups: collapsed_groups.clone(),
+                focus_mode,
+                snoozed_view,
+                overdue_view,
+                category_filter: category_filter.clone(),
+                day_filter,
+                split_view,
+                focused_pane,
+                sort_view: (sort_view != default_sort).then(|| sort_view.map_or("none", SortKey::name).to_string()),
+                spacious: (spacious != default_spacious).then_some(spacious),
+            };
+            if prefs != saved_prefs {
+                save_prefs(&prefs);
+                saved_prefs = prefs;
+            }
+            if toast_message.as_ref().is_some_and(|(_, expires_at)| Instant::now() >= *expires_at) {
+                toast_message = None;
+            }
+
+            let break_length = Duration::from_secs(config.break_minutes * 60);
+            // Drop references to tasks that have been deleted
+            if active_timer.is_some_and(|(id, _)| index_of(&tasks, id).is_none()) {
+                active_timer = None;
+            }
+            if pomodoro
+                .as_ref()
+                .is_some_and(|pomo| pomo.phase == PomodoroPhase::Work && index_of(&tasks, pomo.task_id).is_none())
+            {
+                pomodoro = None;
+            }
+
+            let selected_id = tasks.get(selected).map(|task| task.id);
+            let finished = tick_pomodoro(&mut pomodoro, selected_id, break_length);
+            match finished {
+                Some(PomodoroPhase::Work) => {
+                    let task_id = pomodoro.as_ref().map_or(0, |pomo| pomo.task_id);
+                    if let Some(task) = tasks.iter_mut().find(|task| task.id == task_id) {
+                        task.pomodoros += 1;
+                        dirty = true;
+                    }
+                    if config.pomodoro_notify {
+                        notify("Pomodoro complete, time for a break");
+                    }
+                    if debug_mode {
+                        debug_log.push(format!("Pomodoro complete on task #{}", task_id));
+                    }
+                }
+                Some(PomodoroPhase::Break) => {
+                    if config.pomodoro_notify {
+                        notify("Break over, back to work");
+                    }
+                    if debug_mode {
+                        debug_log.push("Pomodoro break over".to_string());
+                    }
+                }
+                None => {}
+            }
+            // BEL beeps (or flashes) without moving the cursor, so it can't disturb the frame
+            if finished.is_some() && config.pomodoro_bell {
+                let backend = terminal.backend_mut();
+                let _ = backend.write_all(b"\x07").and_then(|_| backend.flush());
+            }
+
+            let today = Local::now().date_naive();
+            let now = Local::now();
+            reminders.retain(|id, _| {
+                index_of(&tasks, *id).is_some_and(|i| !tasks[i].done && tasks[i].reminder_interval_mins.is_some())
+            });
+            if last_reminder.is_some_and(|id| !reminders.contains_key(&id)) {
+                last_reminder = None;
+            }
+            for task in tasks.iter().filter(|task| !task.done && !task.separator) {
+                let Some(interval) = task.reminder_interval_mins else { continue };
+                // The first reminder comes a full interval after the app sees the task
+                let due = reminders
+                    .entry(task.id)
+                    .or_insert_with(|| now + chrono::Duration::minutes(interval as i64));
+                if now >= *due {
+                    *due = next_reminder(*due, interval, now);
+                    last_reminder = Some(task.id);
+                    toast_message = Some(toast(format!("🔔 {} · x snoozes for an hour", task.text)));
+                    if config.reminder_notify {
+                        notify(&format!("Reminder: {}", task.text));
+                    }
+                    if debug_mode {
+                        debug_log.push(format!("Reminder for task #{} went off", task.id));
+                    }
+                }
+            }
+            let plain_view = search_query.is_empty()
+                && !agenda_view
+                && !split_view
+                && !group_view
+                && !overdue_view
+                && category_filter.is_none()
+                && day_filter.is_none()
+                && sort_view.is_none();
+            let mut matches: Vec<(usize, Vec<usize>)> = fuzzy_rank(&tasks, &search_query)
+                .into_iter()
+                .filter(|(i, _)| is_snoozed(&tasks[*i], now) == snoozed_view)
+                .filter(|(i, _)| !overdue_view || is_overdue(&tasks[*i], now))
+                .filter(|(i, _)| category_filter.is_none() || tasks[*i].category == category_filter)
+                .filter(|(i, _)| day_filter.is_none() || tasks[*i].due == day_filter)
+                // Separators only structure the plain list, in file order
+                .filter(|(i, _)| !tasks[*i].separator || plain_view)
+                .collect();
+            // Views below reorder the matches, so the ranking is kept aside first
+            let ranked: Option<Vec<usize>> = jump_to_best.then(|| matches.iter().map(|(i, _)| *i).collect());
+            if let Some(key) = sort_view.filter(|_| search_query.is_empty()) {
+                let mut rank = vec![0; tasks.len()];
+                for (position, i) in sorted_order(&tasks, key).into_iter().enumerate() {
+                    rank[i] = position;
+                }
+                matches.sort_by_key(|(i, _)| rank[*i]);
+            }
+            if done_last_view {
+                // Stable, so open and done tasks each keep their relative order
+                matches.sort_by_key(|(i, _)| tasks[*i].done);
+            }
+            // In the split view `matches` becomes the focused pane, which is all
+            // navigation sees; the other pane is only drawn
+            let mut other_matches = Vec::new();
+            if split_view {
+                let (mut scheduled, inbox): (Vec<_>, Vec<_>) = matches.into_iter().partition(|(i, _)| tasks[*i].due.is_some());
+                scheduled.sort_by_key(|(i, _)| tasks[*i].due_at());
+                (matches, other_matches) = match focused_pane {
+                    Pane::Inbox => (inbox, scheduled),
+                    Pane::Scheduled => (scheduled, inbox),
+                };
+            }
+            let rows = if group_view {
+                group_rows(matches, &tasks, &collapsed_groups)
+            } else {
+                list_rows(matches, &tasks, agenda_view, now.naive_local())
+            };
+            let other_rows = list_rows(other_matches, &tasks, false, now.naive_local());
+            // The heading under the cursor may have emptied out or the view changed
+            if selected_group
+                .as_ref()
+                .is_some_and(|name| !rows.iter().any(|row| matches!(row, Row::Group(group, ..) if group == name)))
+            {
+                selected_group = None;
+            }
+            // Task indices in display order; navigation and numbering follow this
+            let visible: Vec<usize> = rows
+                .iter()
+                .filter_map(|row| match row {
+                    Row::Task(i, _) => Some(*i),
+                    Row::Header(_) | Row::Group(..) => None,
+                })
+                .collect();
+            if typeahead.as_ref().is_some_and(|(_, typed_at)| typed_at.elapsed() >= TYPEAHEAD_TIMEOUT) {
+                typeahead = None;
+            }
+            if number_input.is_some_and(|(_, typed_at)| typed_at.elapsed() >= NUMBER_TIMEOUT) {
+                let (number, _) = number_input.take().unwrap();
+                selected = jump_target(&visible, number).unwrap_or(selected);
+                // Numbers count rows, so a task listed twice is reached in the group numbered
+                if let Some((pos, _)) = numbered_row(&rows, number.clamp(1, visible.len().max(1))) {
+                    selected_in = group_of(&rows, pos).map(str::to_string);
+                    selected_group = None;
+                }
+                if debug_mode {
+                    debug_log.push(format!("Jumped to task number {}", number));
+                }
+            }
+            if let Some(ranked) = ranked {
+                jump_to_best = false;
+                if let Some(best) = best_listed(&ranked, &visible) {
+                    selected = best;
+                    selected_group = None;
+                }
+            }
+            // Any handler may have shrunk or filtered the list since the last frame
+            selected = clamp_selection(selected, &visible);
+            selected_in = match group_view {
+                true => task_row(&rows, selected, selected_in.as_deref()).and_then(|pos| group_of(&rows, pos)).map(str::to_string),
+                false => None,
+            };
+            marked.retain(|id| index_of(&tasks, *id).is_some());
+            if pending_block.is_some_and(|id| index_of(&tasks, id).is_none()) {
+                pending_block = None;
+            }
+            // Task actions are no-ops when the list (or the filtered view) is
+            // empty, or the cursor is on a group heading
+            let has_selection = visible.contains(&selected) && selected_group.is_none();
+            // Separators can be deleted and moved but not done, timed or scheduled
+            let has_task = has_selection && !tasks[selected].separator;
+
+            let mut frame_state = FrameState {
+                tasks: &tasks,
+                config: &config,
+                app_mode: &app_mode,
+                mini,
+                debug_mode,
+                ui_visible,
+                focus_mode,
+                no_color,
+                read_only,
+                rows: &rows,
+                other_rows: &other_rows,
+                visible: &visible,
+                selected,
+                selected_id,
+                has_selection,
+                selected_group: selected_group.as_deref(),
+                selected_in: selected_in.as_deref(),
+                active_timer,
+                snoozed_view,
+                show_details,
+                spacious,
+                group_view,
+                agenda_view,
+                overdue_view,
+                split_view,
+                focused_pane,
+                sort_view,
+                category_filter: category_filter.as_deref(),
+                day_filter,
+                search_query: &search_query,
+                marked: &marked,
+                pending_block,
+                pending_delete,
+                pending_replace: pending_replace.as_ref(),
+                dirty,
+                saves_in_flight,
+                load_warning: load_warning.as_deref(),
+                save_error: save_error.as_deref(),
+                prompt_error: prompt_error.as_deref(),
+                pomodoro: pomodoro.as_ref(),
+                number_input: number_input.map(|(number, _)| number),
+                typeahead: typeahead.as_ref().map(|(prefix, _)| prefix.as_str()),
+                toast: toast_message.as_ref().map(|(message, _)| message.as_str()),
+                add_due_today,
+                input_text: &input_text,
+                now,
+                today,
+                calendar_day,
+                debug_log: &debug_log,
+                help_scroll: &mut help_scroll,
+                help_height: &mut help_height,
+                editor: editor.as_mut(),
+            };
+            terminal.draw(|f| render(f, &mut frame_state))?;
+
+            let event = if event::poll(std::time::Duration::from_millis(200))? { Some(event::read()?) } else { None };
+            if let Some(Event::Resize(width, height)) = event {
+                // Redraw straight away at the new size rather than on the next poll
+                terminal.resize(Rect::new(0, 0, width, height))?;
+                if debug_mode {
+                    debug_log.push(format!("Terminal resized to {}x{}", width, height));
+                }
+                continue;
+            }
+            if let Some(Event::Key(key)) = event {
+                if debug_mode {
+                    debug_log.push(format!("Key pressed: {:?} with modifiers: {:?}", key.code, key.modifiers));
+                    if debug_log.len() > 20 {
+                        debug_log.remove(0);
+                    }
+                }
+                load_warning = None;
+
+                // Raw mode delivers Ctrl+C as a key rather than SIGINT, so it is
+                // handled here, in every mode, as the same clean exit as `q`
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    // A second Ctrl+C at the timer question quits without asking again
+                    if app_mode != AppMode::ConfirmingQuit && quit_needs_confirm(&config, active_timer, pomodoro.as_ref()) {
+                        app_mode = AppMode::ConfirmingQuit;
+                        continue;
+                    }
+                    if debug_mode {
+                        debug_log.push("Quitting on Ctrl+C".to_string());
+                    }
+                    break;
+                }
+
+                // Set by `r`, or `y` at its prompt, and done once the key is handled
+                let mut reload = false;
+                match app_mode {
+                    AppMode::Normal => {
+                        // A pending task number only survives more digits, Backspace or the Enter that commits it
+                        let pending_number = number_input.take();
+                        let pending_typeahead = typeahead.take();
+                        match (key.code, key.modifiers) {
+                            (KeyCode::Char(c @ '0'..='9'), KeyModifiers::NONE | KeyModifiers::SHIFT) if ui_visible => {
+                                let number = pending_number.map_or(0, |(n, _)| n);
+                                let digit = c.to_digit(10).unwrap_or(0) as usize;
+                                number_input = Some((number.saturating_mul(10).saturating_add(digit), Instant::now()));
+                            }
+                            (KeyCode::Backspace, _) if pending_number.is_some() => {
+                                number_input = pending_number
+                                    .map(|(n, _)| (n / 10, Instant::now()))
+                                    .filter(|(n, _)| *n > 0);
+                            }
+                            (KeyCode::Enter, _) if pending_number.is_some() => {
+                                let number = pending_number.map_or(0, |(n, _)| n);
+                                let old_selected = selected;
+                                selected = jump_target(&visible, number).unwrap_or(selected);
+                                if debug_mode {
+                                    debug_log.push(format!("Jumped to task number {}: {} -> {}", number, old_selected, selected));
+                                }
+                            }
+                            (KeyCode::Esc, _) if pending_number.is_some() => {}
+                            (KeyCode::Char(' ') | KeyCode::Enter, KeyModifiers::NONE) if ui_visible && selected_group.is_some() => {
+                                let name = selected_group.clone().unwrap_or_default();
+                                let collapsed = !collapsed_groups.remove(&name);
+                                if collapsed {
+                                    collapsed_groups.insert(name.clone());
+                                }
+                                if debug_mode {
+                                    debug_log.push(format!("Group {} collapsed: {}", name, collapsed));
+                                }
+                            }
+                            (KeyCode::Char(' ' | 'a' | 'A' | 'B' | 'd' | 'D' | 'e' | 'E' | 'R' | 't' | 'P' | 'p' | 'z' | 'c' | 'u' | 'w' | 'W' | '<' | '>'), KeyModifiers::NONE | KeyModifiers::SHIFT)
+                                if read_only && ui_visible =>
+                            {
+                                toast_message = Some(toast("Read-only mode"));
+                            }
+                            (KeyCode::Char('1'..='9'), KeyModifiers::ALT) if read_only && ui_visible => {
+                                toast_message = Some(toast("Read-only mode"));
+                            }
+                            // Alt+digit toggles that row straight away; plain digits only jump to it
+                            (KeyCode::Char(c @ '1'..='9'), KeyModifiers::ALT) if ui_visible => {
+                                let number = c.to_digit(10).unwrap_or(0) as usize;
+                                match numbered_row(&rows, number).map(|(_, index)| index) {
+                                    Some(index) if tasks[index].separator => {
+                                        toast_message = Some(toast(format!("Task {} is a separator", number)));
+                                    }
+                                    Some(index) if config.strict_blocking && !tasks[index].done && open_blocker(&tasks, &tasks[index]).is_some() => {
+                                        toast_message = Some(toast(blocked_message(&tasks, &tasks[index])));
+                                    }
+                                    Some(index) => {
+                                        tasks[index].toggle_done(Local::now());
+                                        dirty = true;
+                                        if debug_mode {
+                                            debug_log.push(format!("Task {} toggled with Alt+{}: done={}", index, number, tasks[index].done));
+                                        }
+                                    }
+                                    None => {
+                                        if debug_mode {
+                                            debug_log.push(format!("Alt+{} ignored, the list shows {}", number, visible.len()));
+                                        }
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('q'), _) if quit_needs_confirm(&config, active_timer, pomodoro.as_ref()) => {
+                                app_mode = AppMode::ConfirmingQuit;
+                            }
+                            (KeyCode::Char('q'), _) => {
+                                if debug_mode {
+                                    debug_log.push("Quitting application".to_string());
+                                }
+                                break;
+                            }
+                            (KeyCode::Char(' '), KeyModifiers::CONTROL) => {
+                                ui_visible = !ui_visible;
+                                if debug_mode {
+                                    debug_log.push(format!("UI toggled: visible={}", ui_visible));
+                                }
+                            }
+                            // With tasks marked, Space, d and Ctrl+↑/↓ act on all of them instead
+                            (KeyCode::Char(' '), KeyModifiers::NONE) if ui_visible && !marked.is_empty() => {
+                                // Completes the lot unless they are all done already, then reopens them
+                                let done = tasks.iter().any(|task| marked.contains(&task.id) && !task.separator && !task.done);
+                                let now = Local::now();
+                                for task in tasks.iter_mut().filter(|task| marked.contains(&task.id) && !task.separator && task.done != done) {
+                                    task.toggle_done(now);
+                                }
+                                dirty = true;
+                                toast_message = Some(toast(format!("{} {} marked task(s)", if done { "Completed" } else { "Reopened" }, marked.len())));
+                                marked.clear();
+                            }
+                            (KeyCode::Char('d'), _) if ui_visible && !marked.is_empty() && !config.confirm_delete => {
+                                last_deleted = delete_marked(&mut tasks, &mut selected, &mut marked);
+                                dirty = true;
+                                toast_message = Some(toast(format!("{} task(s) deleted — u to undo", last_deleted.len())));
+                            }
+                            (KeyCode::Char('d'), _) if ui_visible && !marked.is_empty() => {
+                                app_mode = AppMode::ConfirmingDeleteMarked;
+                            }
+                            (KeyCode::Up | KeyCode::Down, KeyModifiers::CONTROL) if ui_visible && !read_only && !marked.is_empty() => {
+                                // Moves rearrange the manual order, so that is what has to be on screen
+                                sort_view = None;
+                                let to_top = key.code == KeyCode::Up;
+                                let remap = move_all_to_end(&mut tasks, &marked, to_top);
+                                selected = remap_selection(&remap, selected);
+                                dirty = true;
+                                if debug_mode {
+                                    let end = if to_top { "top" } else { "bottom" };
+                                    debug_log.push(format!("{} marked task(s) moved to {}", marked.len(), end));
+                                }
+                                marked.clear();
+                            }
+                            (KeyCode::Char('m'), _) if ui_visible && has_task => {
+                                let id = tasks[selected].id;
+                                if !marked.remove(&id) {
+                                    marked.insert(id);
+                                }
+                                // Marking walks down the list so a run of tasks is quick to pick
+                                selected = step_selection(&visible, selected, true, false);
+                            }
+                            (KeyCode::Esc, _) if !marked.is_empty() => {
+                                marked.clear();
+                                toast_message = Some(toast("Marks cleared"));
+                            }
+                            (KeyCode::Char(' ' | 'w'), _) if ui_visible && has_task && config.strict_blocking && !tasks[selected].done
+                                && open_blocker(&tasks, &tasks[selected]).is_some() =>
+                            {
+                                toast_message = Some(toast(blocked_message(&tasks, &tasks[selected])));
+                            }
+                            (KeyCode::Char(' '), _) if ui_visible && has_task => {
+                                let mut task_toggled = false;
+                                let mut new_done_state = false;
+                                if let Some(task) = tasks.get_mut(selected) {
+                                    task.toggle_done(Local::now());
+                                    new_done_state = task.done;
+                                    task_toggled = true;
+                                }
+                                if task_toggled {
+                                    dirty = true;
+                                    if debug_mode {
+                                        debug_log.push(format!("Task {} toggled: done={}", selected, new_done_state));
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('w'), _) if ui_visible && has_task => {
+                                tasks[selected].cycle_status(Local::now());
+                                dirty = true;
+                                if debug_mode {
+                                    debug_log.push(format!("Task {} status: {:?}", selected, tasks[selected].status()));
+                                }
+                            }
+                            (KeyCode::Char('W'), _) if ui_visible && has_task => {
+                                let cleared = start_only(&mut tasks, selected, Local::now());
+                                dirty = true;
+                                if cleared > 0 {
+                                    toast_message = Some(toast(format!("Moved {} other task(s) back to todo", cleared)));
+                                }
+                                if debug_mode {
+                                    debug_log.push(format!("Task {} is the only one in progress", selected));
+                                }
+                            }
+                            (KeyCode::Char('t'), _) if ui_visible && has_task => {
+                                let previous_id = active_timer.map(|(id, _)| id);
+                                if let Some((id, elapsed)) = stop_timer(&mut tasks, &mut active_timer) {
+                                    dirty = true;
+                                    if debug_mode {
+                                        debug_log.push(format!("Timer stopped on task #{}: +{}s", id, elapsed));
+                                    }
+                                }
+                                let id = tasks[selected].id;
+                                if previous_id != Some(id) {
+                                    active_timer = Some((id, Instant::now()));
+                                    if debug_mode {
+                                        debug_log.push(format!("Timer started on task #{}", id));
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('P'), _) if ui_visible && has_task => {
+                                if pomodoro.take().is_some() {
+                                    if debug_mode {
+                                        debug_log.push("Pomodoro cancelled".to_string());
+                                    }
+                                } else {
+                                    pomodoro = Some(Pomodoro {
+                                        task_id: tasks[selected].id,
+                                        phase: PomodoroPhase::Work,
+                                        remaining: Duration::from_secs(config.pomodoro_minutes * 60),
+                                        last_tick: Instant::now(),
+                                    });
+                                    if debug_mode {
+                                        debug_log.push(format!("Pomodoro started on task {}", selected));
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('a' | 'A'), _) if ui_visible => {
+                                app_mode = AppMode::AddingTask;
+                                add_due_today = key.code == KeyCode::Char('A');
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push(format!("Entered task creation mode (due today: {})", add_due_today));
+                                }
+                            }
+                            (KeyCode::Char('d'), _) if ui_visible && has_selection && !config.confirm_delete => {
+                                let index = selected;
+                                last_deleted = vec![(index, tasks[index].clone())];
+                                let remap = delete_task(&mut tasks, index);
+                                selected = remap_selection(&remap, index);
+                                dirty = true;
+                                toast_message = Some(toast("Task deleted — u to undo"));
+                                if debug_mode {
+                                    debug_log.push(format!("Deleted task without confirmation: {}", index));
+                                }
+                            }
+                            (KeyCode::Char('d'), _) if ui_visible && has_selection => {
+                                app_mode = AppMode::ConfirmingDelete;
+                                pending_delete = Some(tasks[selected].id);
+                                if debug_mode {
+                                    debug_log.push("Entered delete confirmation mode".to_string());
+                                }
+                            }
+                            (KeyCode::Char('u'), _) if ui_visible && last_deleted.is_empty() => {
+                                toast_message = Some(toast("Nothing to undo"));
+                            }
+                            (KeyCode::Char('u'), _) if ui_visible => {
+                                let restored = last_deleted.len();
+                                // Oldest position first, so each task lands back where it was
+                                for (n, (index, task)) in std::mem::take(&mut last_deleted).into_iter().enumerate() {
+                                    let index = index.min(tasks.len());
+                                    if n == 0 {
+                                        selected = index;
+                                    }
+                                    tasks.insert(index, task);
+                                }
+                                dirty = true;
+                                toast_message = Some(toast(if restored == 1 { "Task restored".to_string() } else { format!("{} tasks restored", restored) }));
+                            }
+                            (KeyCode::Char('D'), _) if ui_visible && has_task => {
+                                app_mode = AppMode::SettingDue;
+                                edit_target = Some(tasks[selected].id);
+                                // Always ISO here, as that's what the prompt parses
+                                input_text = tasks[selected].due_label(DEFAULT_DATE_FORMAT).unwrap_or_default();
+                                if debug_mode {
+                                    debug_log.push("Entered due date mode".to_string());
+                                }
+                            }
+                            (KeyCode::Char('e'), KeyModifiers::NONE) if ui_visible && has_task => {
+                                app_mode = AppMode::SettingEstimate;
+                                edit_target = Some(tasks[selected].id);
+                                input_text = tasks[selected].estimate_mins.map(|mins| mins.to_string()).unwrap_or_default();
+                                if debug_mode {
+                                    debug_log.push("Entered estimate mode".to_string());
+                                }
+                            }
+                            (KeyCode::Char('E'), _) if ui_visible && has_task => {
+                                app_mode = AppMode::EditingText;
+                                edit_target = Some(tasks[selected].id);
+                                editor = Some(TextArea::new(&tasks[selected].text));
+                                prompt_error = None;
+                                if debug_mode {
+                                    debug_log.push("Entered text editor".to_string());
+                                }
+                            }
+                            (KeyCode::Char('B'), _) if ui_visible && has_task => {
+                                let id = tasks[selected].id;
+                                let message = match pending_block.take().and_then(|blocked| index_of(&tasks, blocked)) {
+                                    None => {
+                                        pending_block = Some(id);
+                                        "Now pick the task it waits on and press B (Esc cancels)".to_string()
+                                    }
+                                    Some(index) if index == selected => {
+                                        tasks[index].blocked_by.clear();
+                                        dirty = true;
+                                        "No longer waits on anything".to_string()
+                                    }
+                                    Some(index) if tasks[index].blocked_by.contains(&id) => {
+                                        tasks[index].blocked_by.retain(|blocker| *blocker != id);
+                                        dirty = true;
+                                        format!("'{}' no longer waits on '{}'", tasks[index].text, tasks[selected].text)
+                                    }
+                                    Some(index) if blocking_cycle(&tasks, tasks[index].id, id) => {
+                                        format!("'{}' already waits on '{}', that would be a loop", tasks[selected].text, tasks[index].text)
+                                    }
+                                    Some(index) => {
+                                        tasks[index].blocked_by.push(id);
+                                        dirty = true;
+                                        format!("'{}' now waits on '{}'", tasks[index].text, tasks[selected].text)
+                                    }
+                                };
+                                toast_message = Some(toast(message));
+                            }
+                            (KeyCode::Esc, _) if pending_block.is_some() => {
+                                pending_block = None;
+                                toast_message = Some(toast("Blocker not set"));
+                            }
+                            (KeyCode::Char('R'), _) if ui_visible && has_task => {
+                                app_mode = AppMode::SettingReminder;
+                                edit_target = Some(tasks[selected].id);
+                                input_text = tasks[selected].reminder_interval_mins.map(|mins| mins.to_string()).unwrap_or_default();
+                                if debug_mode {
+                                    debug_log.push("Entered reminder mode".to_string());
+                                }
+                            }
+                            (KeyCode::Char('x'), _) if ui_visible => {
+                                match last_reminder.take().and_then(|id| reminders.get_mut(&id).map(|due| (id, due))) {
+                                    Some((id, due)) => {
+                                        *due = Local::now() + chrono::Duration::minutes(REMINDER_SNOOZE_MINS);
+                                        if let Some(index) = index_of(&tasks, id) {
+                                            toast_message = Some(toast(format!("Reminder for '{}' snoozed for an hour", tasks[index].text)));
+                                        }
+                                    }
+                                    None => toast_message = Some(toast("No reminder to snooze")),
+                                }
+                            }
+                            (KeyCode::Char('z'), _) if ui_visible && has_task => {
+                                app_mode = AppMode::Snoozing;
+                                edit_target = Some(tasks[selected].id);
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push("Entered snooze mode".to_string());
+                                }
+                            }
+                            (KeyCode::Char('Z'), _) if ui_visible => {
+                                snoozed_view = !snoozed_view;
+                                if debug_mode {
+                                    debug_log.push(format!("Snoozed view toggled: {}", snoozed_view));
+                                }
+                            }
+                            (KeyCode::Char('s'), KeyModifiers::CONTROL) if read_only => {
+                                toast_message = Some(toast("Read-only mode"));
+                            }
+                            (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+                                // Skip the debounce and wait, so the toast reports the real outcome
+                                if save_tx.send((tasks.clone(), last_id)).is_ok() {
+                                    saves_in_flight += 1;
+                                    dirty = false;
+                                    last_save = Instant::now();
+                                }
+                                while saves_in_flight > 0 {
+                                    let Ok(result) = result_rx.recv() else { break };
+                                    saves_in_flight -= 1;
+                                    save_error = result.err().map(|err| err.to_string());
+                                }
+                                toast_message = Some(toast(if save_error.is_some() { "Save failed" } else { "Saved" }));
+                            }
+                            (KeyCode::Char('e'), KeyModifiers::CONTROL) if read_only => {
+                                toast_message = Some(toast("Read-only mode"));
+                            }
+                            (KeyCode::Char('e'), KeyModifiers::CONTROL) => {
+                                // The file on disk has to be current before the editor sees it
+                                if dirty && save_tx.send((tasks.clone(), last_id)).is_ok() {
+                                    saves_in_flight += 1;
+                                    dirty = false;
+                                }
+                                while saves_in_flight > 0 {
+                                    let Ok(result) = result_rx.recv() else { break };
+                                    saves_in_flight -= 1;
+                                    save_error = result.err().map(|err| err.to_string());
+                                }
+                                if save_error.is_some() {
+                                    toast_message = Some(toast("Save failed, not opening the editor"));
+                                } else {
+                                    restore_terminal()?;
+                                    let edited = run_editor("todos.json");
+                                    terminal::enable_raw_mode()?;
+                                    execute!(std::io::stdout(), EnterAlternateScreen)?;
+                                    terminal.clear()?;
+                                    dirty = reload_tasks(&mut tasks, &mut last_id, &mut selected, &mut load_warning);
+                                    toast_message = Some(toast(match &edited {
+                                        Ok(()) => format!("Reloaded {} task(s)", tasks.len()),
+                                        Err(err) => format!("Editor failed: {}", err),
+                                    }));
+                                    if debug_mode {
+                                        debug_log.push(format!("Reloaded todos.json after editing: {:?}", edited));
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('r'), _) if dirty => {
+                                app_mode = AppMode::ConfirmingReload;
+                            }
+                            (KeyCode::Char('r'), _) => {
+                                reload = true;
+                            }
+                            (KeyCode::Char('c'), _) if ui_visible && has_task => {
+                                let names = category_names(config.categories.keys(), &tasks);
+                                let task = &mut tasks[selected];
+                                task.category = next_category(task.category.as_deref(), &names);
+                                dirty = true;
+                                if debug_mode {
+                                    debug_log.push(format!("Task #{} category: {:?}", task.id, task.category));
+                                }
+                            }
+                            (KeyCode::Char('C'), _) if ui_visible => {
+                                let names = category_names(config.categories.keys(), &tasks);
+                                category_filter = next_category(category_filter.as_deref(), &names);
+                                if debug_mode {
+                                    debug_log.push(format!("Category filter: {:?}", category_filter));
+                                }
+                            }
+                            (KeyCode::Char('s'), _) if ui_visible => {
+                                app_mode = AppMode::ShowingStats;
+                                if debug_mode {
+                                    debug_log.push("Showing statistics".to_string());
+                                }
+                            }
+                            (KeyCode::Char('f'), _) if ui_visible => {
+                                focus_mode = !focus_mode;
+                                if debug_mode {
+                                    debug_log.push(format!("Focus mode toggled: {}", focus_mode));
+                                }
+                            }
+                            (KeyCode::Char('b'), _) if ui_visible => {
+                                done_last_view = !done_last_view;
+                                if debug_mode {
+                                    debug_log.push(format!("Done-at-bottom view toggled: {}", done_last_view));
+                                }
+                            }
+                            (KeyCode::Char('O'), _) if ui_visible => {
+                                overdue_view = !overdue_view;
+                                if debug_mode {
+                                    debug_log.push(format!("Overdue view toggled: {}", overdue_view));
+                                }
+                            }
+                            (KeyCode::Char('i'), _) if ui_visible => {
+                                split_view = !split_view;
+                                agenda_view = false;
+                                group_view = false;
+                                selected_group = None;
+                                if debug_mode {
+                                    debug_log.push(format!("Inbox/scheduled view toggled: {}", split_view));
+                                }
+                            }
+                            (KeyCode::Tab | KeyCode::Left | KeyCode::Right, _) if ui_visible && split_view => {
+                                focused_pane = match focused_pane {
+                                    Pane::Inbox => Pane::Scheduled,
+                                    Pane::Scheduled => Pane::Inbox,
+                                };
+                            }
+                            // Moving a task across the split view gives it a date or takes it away
+                            (KeyCode::Char('>'), _) if ui_visible && has_task && tasks[selected].due.is_none() => {
+                                app_mode = AppMode::SettingDue;
+                                edit_target = Some(tasks[selected].id);
+                                input_text = "today".to_string();
+                            }
+                            (KeyCode::Char('<'), _) if ui_visible && has_task && tasks[selected].due.is_some() => {
+                                tasks[selected].due = None;
+                                tasks[selected].due_time = None;
+                                dirty = true;
+                                toast_message = Some(toast("Moved to the inbox"));
+                            }
+                            (KeyCode::Char('G'), _) if ui_visible => {
+                                group_view = !group_view;
+                                split_view = false;
+                                agenda_view = false;
+                                selected_group = None;
+                                if debug_mode {
+                                    debug_log.push(format!("Grouped view toggled: {}", group_view));
+                                }
+                            }
+                            (KeyCode::Char('g'), _) if ui_visible => {
+                                group_view = false;
+                                split_view = false;
+                                agenda_view = !agenda_view;
+                                if debug_mode {
+                                    debug_log.push(format!("Agenda view toggled: {}", agenda_view));
+                                }
+                            }
+                            (KeyCode::Char('p'), _) if ui_visible => {
+                                let pasted: Vec<String> = read_clipboard()
+                                    .map(|text| {
+                                        text.lines()
+                                            .map(str::trim)
+                                            .filter(|line| !line.is_empty())
+                                            .map(str::to_string)
+                                            .collect()
+                                    })
+                                    .unwrap_or_default();
+                                if pasted.is_empty() {
+                                    if debug_mode {
+                                        debug_log.push("Clipboard empty or not text, nothing pasted".to_string());
+                                    }
+                                } else {
+                                    let (mut added, mut cut, mut skipped) = (0, 0, 0);
+                                    for line in &pasted {
+                                        match config.fit_task_text(line) {
+                                            Ok((text, truncated)) => {
+                                                add_task(&mut tasks, &mut last_id, &text);
+                                                added += 1;
+                                                cut += truncated as usize;
+                                            }
+                                            Err(_) => skipped += 1,
+                                        }
+                                    }
+                                    dirty |= added > 0;
+                                    let mut message = format!("Pasted {} task(s)", added);
+                                    if cut > 0 {
+                                        message.push_str(&format!(", {} cut short", cut));
+                                    }
+                                    if skipped > 0 {
+                                        message.push_str(&format!(", {} too long to add", skipped));
+                                    }
+                                    toast_message = Some(toast(message));
+                                    if debug_mode {
+                                        debug_log.push(format!("Pasted {} task(s) from clipboard, {} skipped", added, skipped));
+                                    }
+                                }
+                            }
+                            (KeyCode::Char('v'), _) if ui_visible => {
+                                show_details = !show_details;
+                                if debug_mode {
+                                    debug_log.push(format!("Details toggled: visible={}", show_details));
+                                }
+                            }
+                            (KeyCode::Char('V'), _) if ui_visible => {
+                                spacious = !spacious;
+                                if debug_mode {
+                                    debug_log.push(format!("Spacious rows: {}", spacious));
+                                }
+                            }
+                            (KeyCode::Char(':'), _) if ui_visible => {
+                                app_mode = AppMode::CommandMode;
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push("Entered command mode".to_string());
+                                }
+                            }
+                            (KeyCode::Char('/'), _) if ui_visible => {
+                                app_mode = AppMode::Searching;
+                                if debug_mode {
+                                    debug_log.push("Entered search mode".to_string());
+                                }
+                            }
+                            (KeyCode::Char('M'), _) if ui_visible => {
+                                app_mode = AppMode::ShowingCalendar;
+                                calendar_day = day_filter.unwrap_or(today);
+                                if debug_mode {
+                                    debug_log.push("Showing calendar".to_string());
+                                }
+                            }
+                            (KeyCode::Esc, _) if !search_query.is_empty() => {
+                                search_query.clear();
+                                if debug_mode {
+                                    debug_log.push("Search cleared".to_string());
+                                }
+                            }
+                            (KeyCode::Char('h'), _) if ui_visible => {
+                                app_mode = AppMode::ShowingHelp;
+                                help_scroll = 0;
+                                if debug_mode {
+                                    debug_log.push("Showing help".to_string());
+                                }
+                            }
+                            (KeyCode::Up | KeyCode::Down, KeyModifiers::CONTROL) if read_only && ui_visible => {
+                                toast_message = Some(toast("Read-only mode"));
+                            }
+                            (KeyCode::Up | KeyCode::Down, KeyModifiers::CONTROL) if ui_visible && has_selection => {
+                                sort_view = None;
+                                let to_top = key.code == KeyCode::Up;
+                                let target = if to_top { 0 } else { tasks.len() - 1 };
+                                if selected != target {
+                                    let old_selected = selected;
+                                    let remap = move_to_end(&mut tasks, selected, to_top);
+                                    selected = remap_selection(&remap, selected);
+                                    dirty = true;
+                                    if debug_mode {
+                                        let end = if to_top { "top" } else { "bottom" };
+                                        debug_log.push(format!("Task moved to {}: {} -> {}", end, old_selected, selected));
+                                    }
+                                }
+                            }
+                            // Headings take the cursor too in the grouped view, so it can
+                            // reach a collapsed group to expand it
+                            (KeyCode::Up | KeyCode::Down, _) if ui_visible && group_view => {
+                                let current = match &selected_group {
+                                    Some(group) => rows.iter().position(|row| matches!(row, Row::Group(name, ..) if name == group)),
+                                    None => task_row(&rows, selected, selected_in.as_deref()),
+                                };
+                                let target = current.map_or(0, |pos| {
+                                    step_position(pos, rows.len(), key.code == KeyCode::Down, config.wrap_navigation)
+                                });
+                                match rows.get(target) {
+                                    Some(Row::Group(name, ..)) => selected_group = Some(name.clone()),
+                                    Some(Row::Task(i, _)) => {
+                                        selected = *i;
+                                        selected_group = None;
+                                        selected_in = group_of(&rows, target).map(str::to_string);
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            (KeyCode::Down, _) if ui_visible => {
+                                let old_selected = selected;
+                                selected = step_selection(&visible, selected, true, config.wrap_navigation);
+                                if debug_mode && old_selected != selected {
+                                    debug_log.push(format!("Selection moved down: {} -> {}", old_selected, selected));
+                                }
+                            }
+                            (KeyCode::Up, _) if ui_visible => {
+                                let old_selected = selected;
+                                selected = step_selection(&visible, selected, false, config.wrap_navigation);
+                                if debug_mode && old_selected != selected {
+                                    debug_log.push(format!("Selection moved up: {} -> {}", old_selected, selected));
+                                }
+                            }
+                            // Task actions whose guards failed because nothing is selected
+                            (KeyCode::Char('d'), _) if ui_visible => {
+                                toast_message = Some(toast("Nothing to delete"));
+                            }
+                            (KeyCode::Char(' ' | 'w' | 'W' | 't' | 'P' | 'B' | 'D' | 'e' | 'E' | 'R' | 'z' | 'c' | 'm'), KeyModifiers::NONE | KeyModifiers::SHIFT)
+                                if ui_visible && has_selection =>
+                            {
+                                toast_message = Some(toast("That's a separator, not a task"));
+                            }
+                            (KeyCode::Char(' ' | 'w' | 'W' | 't' | 'P' | 'B' | 'D' | 'e' | 'E' | 'R' | 'z' | 'c' | 'm'), KeyModifiers::NONE | KeyModifiers::SHIFT) if ui_visible => {
+                                toast_message = Some(toast("No task selected"));
+                            }
+                            // Letters without an action of their own jump to the
+                            // next task starting with what has been typed
+                            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) if ui_visible && c.is_alphabetic() => {
+                                let mut prefix = pending_typeahead.map(|(prefix, _)| prefix).unwrap_or_default();
+                                prefix.push(c);
+                                let mut target = None;
+                                if prefix.chars().count() > 1 {
+                                    target = typeahead_target(&tasks, &visible, selected, &prefix, true);
+                                }
+                                if target.is_none() {
+                                    // A lone letter, or one that doesn't extend the prefix, starts over
+                                    prefix = c.to_string();
+                                    target = typeahead_target(&tasks, &visible, selected, &prefix, false);
+                                }
+                                match target {
+                                    Some(index) => {
+                                        selected = index;
+                                        selected_group = None;
+                                    }
+                                    None => toast_message = Some(toast(format!("No task starts with '{}'", prefix))),
+                                }
+                                if debug_mode {
+                                    debug_log.push(format!("Typeahead '{}' -> {:?}", prefix, target));
+                                }
+                                typeahead = Some((prefix, Instant::now()));
+                            }
+                            _ => {
+                                if debug_mode {
+                                    debug_log.push("Unhandled key in Normal mode".to_string());
+                                }
+                            }
+                        }
+                    }
+                    AppMode::AddingTask => {
+                        prompt_error = None;
+                        // Only repeated Tabs cycle; any other key settles on the shown tag
+                        let completing = tag_completion.take();
+                        match key.code {
+                            KeyCode::Tab => {
+                                let (prefix, n) = match completing {
+                                    Some((prefix, n)) => (prefix, n + 1),
+                                    None => (input_text.rsplit(char::is_whitespace).next().unwrap_or("").to_string(), 0),
+                                };
+                                if let Some(completed) = complete_tag(&input_text, &all_tags(&tasks), &prefix, n) {
+                                    input_text = completed;
+                                    tag_completion = Some((prefix, n));
+                                }
+                            }
+                            KeyCode::Enter => match config.fit_task_text(input_text.trim()) {
+                                _ if input_text.trim().is_empty() => prompt_error = Some("Task cannot be empty".to_string()),
+                                // Stays in the prompt so the text can be shortened
+                                Err(err) => prompt_error = Some(err),
+                                Ok((text, truncated)) => {
+                                    input_text = text;
+                                    if truncated {
+                                        let max = config.max_task_length.unwrap_or_default();
+                                        toast_message = Some(toast(format!("Text cut to {} characters", max)));
+                                    }
+                                    if config.warn_duplicates && is_duplicate(&tasks, &input_text, config.duplicate_ignore_case) {
+                                        app_mode = AppMode::ConfirmingDuplicate;
+                                    } else {
+                                        let id = add_task(&mut tasks, &mut last_id, &input_text);
+                                        if add_due_today && let Some(index) = index_of(&tasks, id) && !tasks[index].separator {
+                                            tasks[index].due = Some(today);
+                                        }
+                                        dirty = true;
+                                        if !truncated {
+                                            toast_message = Some(toast(if separator_label(&input_text).is_some() { "Separator added" } else { "Task added" }));
+                                        }
+                                        if debug_mode {
+                                            debug_log.push(format!("Added task: '{}'", input_text));
+                                        }
+                                        app_mode = AppMode::Normal;
+                                        input_text.clear();
+                                    }
+                                }
+                            },
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push("Cancelled task creation".to_string());
+                                }
+                            }
+                            KeyCode::Backspace => {
+                                input_text.pop();
+                            }
+                            KeyCode::Char(c) => {
+                                input_text.push(c);
+                            }
+                            _ => {
+                                if debug_mode {
+                                    debug_log.push("Unhandled key in AddingTask mode".to_string());
+                                }
+                            }
+                        }
+                    }
+                    AppMode::SettingDue => {
+                        prompt_error = None;
+                        match key.code {
+                            KeyCode::Enter => match parse_due_input(&input_text, Local::now().date_naive()) {
+                                Ok(due) => {
+                                    if let Some(task) = edit_target.and_then(|id| tasks.iter_mut().find(|task| task.id == id)) {
+                                        task.due = due.map(|(date, _)| date);
+                                        task.due_time = due.and_then(|(_, time)| time);
+                                        dirty = true;
+                                        if debug_mode {
+                                            debug_log.push(format!("Due date of task #{} set to {:?}", task.id, due));
+                                        }
+                                    }
+                                    app_mode = AppMode::Normal;
+                                    edit_target = None;
+                                    input_text.clear();
+                                }
+                                Err(error) => prompt_error = Some(error),
+                            },
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                edit_target = None;
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push("Cancelled due date".to_string());
+                                }
+                            }
+                            code => {
+                                if !edit_input(&mut input_text, code) && debug_mode {
+                                    debug_log.push("Unhandled key in SettingDue mode".to_string());
+                                }
+                            }
+                        }
+                    }
+                    AppMode::SettingEstimate => {
+                        prompt_error = None;
+                        match key.code {
+                            KeyCode::Enter => match parse_minutes(&input_text) {
+                                Ok(estimate) => {
+                                    if let Some(task) = edit_target.and_then(|id| tasks.iter_mut().find(|task| task.id == id)) {
+                                        task.estimate_mins = estimate;
+                                        dirty = true;
+                                        if debug_mode {
+                                            debug_log.push(format!("Estimate of task #{} set to {:?}", task.id, estimate));
+                                        }
+                                    }
+                                    app_mode = AppMode::Normal;
+                                    edit_target = None;
+                                    input_text.clear();
+                                }
+                                Err(error) => prompt_error = Some(error),
+                            },
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                edit_target = None;
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push("Cancelled estimate".to_string());
+                                }
+                            }
+                            code => {
+                                if !edit_input(&mut input_text, code) && debug_mode {
+                                    debug_log.push("Unhandled key in SettingEstimate mode".to_string());
+                                }
+                            }
+                        }
+                    }
+                    AppMode::SettingReminder => {
+                        prompt_error = None;
+                        match key.code {
+                            // Zero would go off on every tick, so it clears like empty input
+                            KeyCode::Enter => match parse_minutes(&input_text).map(|mins| mins.filter(|&mins| mins > 0)) {
+                                Ok(interval) => {
+                                    if let Some(task) = edit_target.and_then(|id| tasks.iter_mut().find(|task| task.id == id)) {
+                                        task.reminder_interval_mins = interval;
+                                        // A new interval starts counting from now
+                                        reminders.remove(&task.id);
+                                        dirty = true;
+                                        if debug_mode {
+                                            debug_log.push(format!("Reminder of task #{} set to {:?}", task.id, interval));
+                                        }
+                                    }
+                                    app_mode = AppMode::Normal;
+                                    edit_target = None;
+                                    input_text.clear();
+                                }
+                                Err(error) => prompt_error = Some(error),
+                            },
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                edit_target = None;
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push("Cancelled reminder".to_string());
+                                }
+                            }
+                            code => {
+                                if !edit_input(&mut input_text, code) && debug_mode {
+                                    debug_log.push("Unhandled key in SettingReminder mode".to_string());
+                                }
+                            }
+                        }
+                    }
+                    AppMode::Snoozing => {
+                        prompt_error = None;
+                        match key.code {
+                            KeyCode::Enter => match parse_snooze_input(&input_text, Local::now()) {
+                                Ok(until) => {
+                                    if let Some(task) = edit_target.and_then(|id| tasks.iter_mut().find(|task| task.id == id)) {
+                                        task.snoozed_until = until;
+                                        dirty = true;
+                                        toast_message = Some(toast(if until.is_some() { "Task snoozed" } else { "Task woken up" }));
+                                        if debug_mode {
+                                            debug_log.push(format!("Task #{} snoozed until {:?}", task.id, until));
+                                        }
+                                    }
+                                    app_mode = AppMode::Normal;
+                                    edit_target = None;
+                                    input_text.clear();
+                                }
+                                Err(error) => prompt_error = Some(error),
+                            },
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                edit_target = None;
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push("Cancelled snooze".to_string());
+                                }
+                            }
+                            code => {
+                                if !edit_input(&mut input_text, code) && debug_mode {
+                                    debug_log.push("Unhandled key in Snoozing mode".to_string());
+                                }
+                            }
+                        }
+                    }
+                    AppMode::ConfirmingReplace => {
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                                if let Some((replacement, _)) = pending_replace.take() {
+                                    let mut changed = 0;
+                                    for task in tasks.iter_mut() {
+                                        if let Some(text) = replace_text(&task.text, &replacement) {
+                                            task.text = text;
+                                            changed += 1;
+                                        }
+                                    }
+                                    dirty = true;
+                                    toast_message = Some(toast(format!("Replaced text in {} task(s)", changed)));
+                                    if debug_mode {
+                                        debug_log.push(format!("Replaced '{}' in {} task(s)", replacement.find, changed));
+                                    }
+                                }
+                                app_mode = AppMode::Normal;
+                            }
+                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                pending_replace = None;
+                                if debug_mode {
+                                    debug_log.push("Cancelled replace".to_string());
+                                }
+                            }
+                            _ => {
+                                if debug_mode {
+                                    debug_log.push("Unhandled key in ConfirmingReplace mode".to_string());
+                                }
+                            }
                         }
                     }
-                    AppMode::Normal | AppMode::ShowingHelp => String::new(),
-                };
-                let prompt_paragraph = Paragraph::new(prompt_text)
-                    .block(Block::default().borders(Borders::ALL).title("Prompt"));
-                
-                let prompt_index = if debug_mode { 1 } else { 1 };
-                f.render_widget(prompt_paragraph, main_chunks[prompt_index]);
-            }
-            
-            // Help overlay
-            if app_mode == AppMode::ShowingHelp {
-                let help_text = "GOTTODO - Keyboard Shortcuts\n\n\
-                    Navigation:\n\
-                    • ↑/↓        Navigate tasks\n\
-                    • Space      Toggle task completion\n\
-                    • q          Quit application\n\n\
-                    Task Management:\n\
-                    • a          Add new task\n\
-                    • d          Delete selected task\n\n\
-                    Interface:\n\
-                    • Ctrl+Space Hide/show todo list\n\
-                    • h          Show/hide this help\n\
-                    • Esc        Close help or cancel action\n\n\
-                    Press any key to close this help...";
-                
-                let help_paragraph = Paragraph::new(help_text)
-                    .block(Block::default().borders(Borders::ALL).title("Help"));
-                f.render_widget(help_paragraph, main_chunks[0]);
-            }
-            
-            // Debug area at bottom (always show if debug mode is on)
-            if debug_mode {
-                let debug_text = debug_log.iter().rev().take(6).rev().cloned().collect::<Vec<_>>().join("\n");
-                let debug_paragraph = Paragraph::new(debug_text)
-                    .block(Block::default().borders(Borders::ALL).title("Debug Log"));
-                
-                let debug_index = if app_mode != AppMode::Normal { 2 } else { 1 };
-                f.render_widget(debug_paragraph, main_chunks[debug_index]);
-            }
-        })?;
-
-        if event::poll(std::time::Duration::from_millis(200))? {
-            if let Event::Key(key) = event::read()? {
-                if debug_mode {
-                    debug_log.push(format!("Key pressed: {:?} with modifiers: {:?}", key.code, key.modifiers));
-                    if debug_log.len() > 20 {
-                        debug_log.remove(0);
+                    AppMode::ConfirmingClearAll => {
+                        prompt_error = None;
+                        match key.code {
+                            KeyCode::Enter if input_text.trim() == "yes" => {
+                                // Keep a copy so a mistaken clear can be undone by hand
+                                let backup = serde_json::to_string_pretty(&tasks)
+                                    .map_err(|err| err.to_string())
+                                    .and_then(|json| std::fs::write(CLEARED_FILE, json).map_err(|err| err.to_string()));
+                                match backup {
+                                    Ok(()) => {
+                                        let cleared = tasks.len();
+                                        tasks.clear();
+                                        selected = 0;
+                                        selected_group = None;
+                                        dirty = true;
+                                        toast_message = Some(toast(format!(
+                                            "Cleared {} task(s); a copy is in {}",
+                                            cleared, CLEARED_FILE
+                                        )));
+                                        if debug_mode {
+                                            debug_log.push(format!("Cleared all {} task(s)", cleared));
+                                        }
+                                        app_mode = AppMode::Normal;
+                                        input_text.clear();
+                                    }
+                                    Err(err) => prompt_error = Some(format!("Backup failed, nothing cleared: {}", err)),
+                                }
+                            }
+                            KeyCode::Enter => prompt_error = Some("Type yes to confirm".to_string()),
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                input_text.clear();
+                                if debug_mode {
+                                    debug_log.push("Cancelled clear-all".to_string());
+                                }
+                            }
+                            code => {
+                                if !edit_input(&mut input_text, code) && debug_mode {
+                                    debug_log.push("Unhandled key in ConfirmingClearAll mode".to_string());
+                                }
+                            }
+                        }
                     }
-                }
-                
-                match app_mode {
-                    AppMode::Normal => {
-                        match (key.code, key.modifiers) {
-                            (KeyCode::Char('q'), _) => {
+                    AppMode::ConfirmingDuplicate => {
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                                let id = add_task(&mut tasks, &mut last_id, input_text.trim());
+                                if add_due_today && let Some(index) = index_of(&tasks, id) && !tasks[index].separator {
+                                    tasks[index].due = Some(today);
+                                }
+                                dirty = true;
+                                toast_message = Some(toast(if separator_label(&input_text).is_some() { "Separator added" } else { "Task added" }));
                                 if debug_mode {
-                                    debug_log.push("Quitting application".to_string());
+                                    debug_log.push(format!("Added duplicate task: '{}'", input_text.trim()));
                                 }
-                                break;
+                                app_mode = AppMode::Normal;
+                                input_text.clear();
                             }
-                            (KeyCode::Char(' '), KeyModifiers::CONTROL) => {
-                                ui_visible = !ui_visible;
+                            KeyCode::Char('n') | KeyCode::Char('N') => {
+                                // Back to the add prompt with the text kept for editing
+                                app_mode = AppMode::AddingTask;
+                            }
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                input_text.clear();
                                 if debug_mode {
-                                    debug_log.push(format!("UI toggled: visible={}", ui_visible));
+                                    debug_log.push("Cancelled duplicate task".to_string());
                                 }
                             }
-                            (KeyCode::Char(' '), _) if ui_visible => {
-                                let mut task_toggled = false;
-                                let mut new_done_state = false;
-                                if let Some(task) = tasks.get_mut(selected) {
-                                    task.done = !task.done;
-                                    new_done_state = task.done;
-                                    task_toggled = true;
+                            _ => {
+                                if debug_mode {
+                                    debug_log.push("Unhandled key in ConfirmingDuplicate mode".to_string());
                                 }
-                                if task_toggled {
-                                    save_tasks(&tasks);
+                            }
+                        }
+                    }
+                    AppMode::ConfirmingQuit => {
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                                if let Some((id, elapsed)) = stop_timer(&mut tasks, &mut active_timer) {
+                                    dirty = true;
                                     if debug_mode {
-                                        debug_log.push(format!("Task {} toggled: done={}", selected, new_done_state));
+                                        debug_log.push(format!("Timer stopped on task #{} before quitting: +{}s", id, elapsed));
                                     }
                                 }
+                                break;
                             }
-                            (KeyCode::Char('a'), _) if ui_visible => {
-                                app_mode = AppMode::AddingTask;
-                                input_text.clear();
+                            KeyCode::Char('n') | KeyCode::Char('N') => break,
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
                                 if debug_mode {
-                                    debug_log.push("Entered task creation mode".to_string());
+                                    debug_log.push("Cancelled quitting".to_string());
                                 }
                             }
-                            (KeyCode::Char('d'), _) if ui_visible && !tasks.is_empty() => {
-                                app_mode = AppMode::ConfirmingDelete;
+                            _ => {
                                 if debug_mode {
-                                    debug_log.push("Entered delete confirmation mode".to_string());
+                                    debug_log.push("Unhandled key in ConfirmingQuit mode".to_string());
                                 }
                             }
-                            (KeyCode::Char('h'), _) if ui_visible => {
-                                app_mode = AppMode::ShowingHelp;
+                        }
+                    }
+                    AppMode::ConfirmingReload => {
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                                app_mode = AppMode::Normal;
+                                reload = true;
+                            }
+                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
                                 if debug_mode {
-                                    debug_log.push("Showing help".to_string());
+                                    debug_log.push("Cancelled reloading".to_string());
                                 }
                             }
-                            (KeyCode::Down, _) if ui_visible => {
-                                let old_selected = selected;
-                                let max_index = tasks.len().saturating_sub(1);
-                                selected = (selected + 1).min(max_index);
-                                if debug_mode && old_selected != selected {
-                                    debug_log.push(format!("Selection moved down: {} -> {}", old_selected, selected));
+                            _ => {
+                                if debug_mode {
+                                    debug_log.push("Unhandled key in ConfirmingReload mode".to_string());
                                 }
                             }
-                            (KeyCode::Up, _) if ui_visible => {
-                                let old_selected = selected;
-                                if selected > 0 {
-                                    selected -= 1;
+                        }
+                    }
+                    AppMode::ConfirmingDeleteMarked => {
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                                last_deleted = delete_marked(&mut tasks, &mut selected, &mut marked);
+                                dirty = true;
+                                toast_message = Some(toast(format!("{} task(s) deleted — u to undo", last_deleted.len())));
+                                app_mode = AppMode::Normal;
+                            }
+                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                if debug_mode {
+                                    debug_log.push("Cancelled deleting marked tasks".to_string());
                                 }
-                                if debug_mode && old_selected != selected {
-                                    debug_log.push(format!("Selection moved up: {} -> {}", old_selected, selected));
+                            }
+                            _ => {
+                                if debug_mode {
+                                    debug_log.push("Unhandled key in ConfirmingDeleteMarked mode".to_string());
+                                }
+                            }
+                        }
+                    }
+                    AppMode::ConfirmingDelete => {
+                        match key.code {
+                            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                                match delete_pending(&mut tasks, &mut selected, &mut pending_delete) {
+                                    Some((index, task)) => {
+                                        if debug_mode {
+                                            debug_log.push(format!("Deleted task: '{}'", task.text));
+                                        }
+                                        last_deleted = vec![(index, task)];
+                                        dirty = true;
+                                        toast_message = Some(toast("Task deleted — u to undo"));
+                                    }
+                                    None => {
+                                        toast_message = Some(toast("Task changed, nothing deleted"));
+                                        if debug_mode {
+                                            debug_log.push("Delete target no longer matches, skipped".to_string());
+                                        }
+                                    }
+                                }
+                                app_mode = AppMode::Normal;
+                            }
+                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                pending_delete = None;
+                                if debug_mode {
+                                    debug_log.push("Cancelled task deletion".to_string());
                                 }
                             }
                             _ => {
                                 if debug_mode {
-                                    debug_log.push("Unhandled key in Normal mode".to_string());
+                                    debug_log.push("Unhandled key in ConfirmingDelete mode".to_string());
                                 }
                             }
                         }
                     }
-                    AppMode::AddingTask => {
+                    AppMode::Searching => {
                         match key.code {
                             KeyCode::Enter => {
-                                if !input_text.trim().is_empty() {
-                                    tasks.push(Task {
-                                        text: input_text.trim().to_string(),
-                                        done: false,
-                                    });
-                                    save_tasks(&tasks);
-                                    if debug_mode {
-                                        debug_log.push(format!("Added task: '{}'", input_text.trim()));
+                                app_mode = AppMode::Normal;
+                                if debug_mode {
+                                    debug_log.push(format!("Search applied: '{}' ({} matches)", search_query, visible.len()));
+                                }
+                            }
+                            KeyCode::Esc => {
+                                app_mode = AppMode::Normal;
+                                search_query.clear();
+                                if debug_mode {
+                                    debug_log.push("Cancelled search".to_string());
+                                }
+                            }
+                            KeyCode::Down => selected = step_selection(&visible, selected, true, false),
+                            KeyCode::Up => selected = step_selection(&visible, selected, false, false),
+                            KeyCode::Backspace | KeyCode::Char(_) => {
+                                match key.code {
+                                    KeyCode::Char(c) => search_query.push(c),
+                                    _ => {
+                                        search_query.pop();
                                     }
                                 }
+                                jump_to_best = true;
+                            }
+                            _ => {
+                                if debug_mode {
+                                    debug_log.push("Unhandled key in Searching mode".to_string());
+                                }
+                            }
+                        }
+                    }
+                    AppMode::CommandMode => {
+                        prompt_error = None;
+                        match key.code {
+                            KeyCode::Enter => {
+                                let parsed = parse_command(&input_text);
+                                if debug_mode {
+                                    debug_log.push(format!("Command '{}' parsed as {:?}", input_text, parsed));
+                                }
                                 app_mode = AppMode::Normal;
-                                input_text.clear();
+                                match parsed {
+                                    Ok(Command::ClearDone | Command::ClearAll | Command::Toggle(_) | Command::Replace(_)) if read_only => {
+                                        app_mode = AppMode::CommandMode;
+                                        prompt_error = Some("Read-only mode".to_string());
+                                    }
+                                    Ok(Command::Sort(sort_key)) => {
+                                        sort_view = sort_key;
+                                        toast_message = Some(toast(match sort_key {
+                                            Some(key) => format!("Sorted by {} — :sort none for your own order", key.name()),
+                                            None => "Back to your own order".to_string(),
+                                        }));
+                                    }
+                                    Ok(Command::ClearDone) => {
+                                        let order: Vec<usize> = (0..tasks.len()).filter(|&i| !tasks[i].done).collect();
+                                        let cleared = tasks.len() - order.len();
+                                        let remap = apply_order(&mut tasks, &order);
+                                        selected = remap_selection(&remap, selected);
+                                        dirty = true;
+                                        toast_message = Some(toast(format!("Cleared {} done task(s)", cleared)));
+                                    }
+                                    Ok(Command::ClearAll) if tasks.is_empty() => {
+                                        toast_message = Some(toast("Nothing to clear"));
+                                    }
+                                    Ok(Command::ClearAll) => app_mode = AppMode::ConfirmingClearAll,
+                                    Ok(Command::Toggle(number)) => match visible.get(number - 1) {
+                                        Some(&index) if tasks[index].separator => {
+                                            app_mode = AppMode::CommandMode;
+                                            prompt_error = Some(format!("Task {} is a separator", number));
+                                        }
+                                        Some(&index) if config.strict_blocking && !tasks[index].done && open_blocker(&tasks, &tasks[index]).is_some() => {
+                                            app_mode = AppMode::CommandMode;
+                                            prompt_error = Some(blocked_message(&tasks, &tasks[index]));
+                                        }
+                                        Some(&index) => {
+                                            tasks[index].toggle_done(Local::now());
+                                            dirty = true;
+                                            if debug_mode {
+                                                debug_log.push(format!("Task {} toggled: done={}", index, tasks[index].done));
+                                            }
+                                        }
+                                        None => {
+                                            app_mode = AppMode::CommandMode;
+                                            prompt_error = Some(format!("No task {} (the list shows {})", number, visible.len()));
+                                        }
+                                    },
+                                    Ok(Command::ExportOutline(path)) => {
+                                        toast_message = Some(toast(match std::fs::write(&path, export_outline(&tasks)) {
+                                            Ok(()) => format!("Exported {} task(s) to {}", tasks.len(), path),
+                                            Err(err) => format!("Export failed: {}", err),
+                                        }));
+                                    }
+                                    Ok(Command::Export(path)) => {
+                                        toast_message = Some(toast(match export_markdown(&tasks, &path) {
+                                            Ok(()) => format!("Exported {} task(s) to {}", tasks.len(), path),
+                                            Err(err) => format!("Export failed: {}", err),
+                                        }));
+                                    }
+                                    Ok(Command::Replace(replacement)) => {
+                                        let count = tasks
+                                            .iter()
+                                            .filter(|task| replace_text(&task.text, &replacement).is_some())
+                                            .count();
+                                        if count == 0 {
+                                            toast_message = Some(toast(format!("No tasks contain '{}'", replacement.find)));
+                                        } else {
+                                            pending_replace = Some((replacement, count));
+                                            app_mode = AppMode::ConfirmingReplace;
+                                        }
+                                    }
+                                    Ok(Command::Help) => {
+                                        app_mode = AppMode::ShowingHelp;
+                                        help_scroll = 0;
+                                    }
+                                    Ok(Command::Quit) if quit_needs_confirm(&config, active_timer, pomodoro.as_ref()) => {
+                                        app_mode = AppMode::ConfirmingQuit;
+                                    }
+                                    Ok(Command::Quit) => break,
+                                    Err(error) => {
+                                        app_mode = AppMode::CommandMode;
+                                        prompt_error = Some(error);
+                                    }
+                                }
+                                if app_mode != AppMode::CommandMode {
+                                    input_text.clear();
+                                }
                             }
                             KeyCode::Esc => {
                                 app_mode = AppMode::Normal;
                                 input_text.clear();
                                 if debug_mode {
-                                    debug_log.push("Cancelled task creation".to_string());
+                                    debug_log.push("Cancelled command".to_string());
                                 }
                             }
                             KeyCode::Backspace => {
@@ -255,51 +3762,603 @@ fn main() -> std::io::Result<()> {
                             }
                             _ => {
                                 if debug_mode {
-                                    debug_log.push("Unhandled key in AddingTask mode".to_string());
+                                    debug_log.push("Unhandled key in CommandMode".to_string());
                                 }
                             }
                         }
                     }
-                    AppMode::ConfirmingDelete => {
-                        match key.code {
-                            KeyCode::Char('y') | KeyCode::Char('Y') => {
-                                if selected < tasks.len() {
-                                    let removed_task = tasks.remove(selected);
-                                    if selected >= tasks.len() && tasks.len() > 0 {
-                                        selected = tasks.len() - 1;
-                                    }
-                                    save_tasks(&tasks);
-                                    if debug_mode {
-                                        debug_log.push(format!("Deleted task: '{}'", removed_task.text));
+                    AppMode::EditingText => match (key.code, key.modifiers) {
+                        (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+                            // The text field is a single line, so line breaks become spaces
+                            let text = editor.as_ref().map(TextArea::text).unwrap_or_default();
+                            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                            match edit_target.and_then(|id| index_of(&tasks, id)) {
+                                _ if text.is_empty() => prompt_error = Some("Task cannot be empty".to_string()),
+                                Some(index) => match config.fit_task_text(&text) {
+                                    Ok((text, truncated)) => {
+                                        let max = config.max_task_length.unwrap_or_default();
+                                        toast_message = Some(toast(if truncated { format!("Text cut to {} characters", max) } else { "Task updated".to_string() }));
+                                        tasks[index].text = text;
+                                        dirty = true;
+                                        app_mode = AppMode::Normal;
+                                        editor = None;
+                                        prompt_error = None;
                                     }
+                                    Err(err) => prompt_error = Some(err),
+                                },
+                                None => {
+                                    toast_message = Some(toast("Task changed, nothing saved"));
+                                    app_mode = AppMode::Normal;
+                                    editor = None;
+                                    prompt_error = None;
                                 }
+                            }
+                        }
+                        (KeyCode::Esc, _) => {
+                            app_mode = AppMode::Normal;
+                            editor = None;
+                            prompt_error = None;
+                            if debug_mode {
+                                debug_log.push("Cancelled text editor".to_string());
+                            }
+                        }
+                        (KeyCode::Char(_), modifiers) if modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                            if debug_mode {
+                                debug_log.push("Unhandled key in EditingText mode".to_string());
+                            }
+                        }
+                        (code, _) => {
+                            if !editor.as_mut().is_some_and(|editor| editor.handle(code)) && debug_mode {
+                                debug_log.push("Unhandled key in EditingText mode".to_string());
+                            }
+                        }
+                    },
+                    // Scrolling past the end is clamped when the overlay is drawn
+                    AppMode::ShowingHelp => match key.code {
+                        KeyCode::Up | KeyCode::Char('k') => help_scroll = help_scroll.saturating_sub(1),
+                        KeyCode::Down | KeyCode::Char('j') => help_scroll = help_scroll.saturating_add(1),
+                        KeyCode::PageUp => help_scroll = help_scroll.saturating_sub(help_height.max(1)),
+                        KeyCode::PageDown | KeyCode::Char(' ') => help_scroll = help_scroll.saturating_add(help_height.max(1)),
+                        KeyCode::Home => help_scroll = 0,
+                        KeyCode::End => help_scroll = u16::MAX,
+                        KeyCode::Esc | KeyCode::Char('q' | 'h') => {
+                            app_mode = AppMode::Normal;
+                            if debug_mode {
+                                debug_log.push("Closed help".to_string());
+                            }
+                        }
+                        _ => {
+                            if debug_mode {
+                                debug_log.push("Unhandled key in ShowingHelp mode".to_string());
+                            }
+                        }
+                    },
+                    AppMode::ShowingCalendar => {
+                        match key.code {
+                            KeyCode::Left => calendar_day = calendar_day - Days::new(1),
+                            KeyCode::Right => calendar_day = calendar_day + Days::new(1),
+                            KeyCode::Up => calendar_day = calendar_day - Days::new(7),
+                            KeyCode::Down => calendar_day = calendar_day + Days::new(7),
+                            KeyCode::Char('<') | KeyCode::PageUp => calendar_day = calendar_day - Months::new(1),
+                            KeyCode::Char('>') | KeyCode::PageDown => calendar_day = calendar_day + Months::new(1),
+                            KeyCode::Enter => {
+                                day_filter = Some(calendar_day);
                                 app_mode = AppMode::Normal;
+                                if debug_mode {
+                                    debug_log.push(format!("Filtering to tasks due {}", calendar_day));
+                                }
                             }
-                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                            KeyCode::Backspace => {
+                                day_filter = None;
                                 app_mode = AppMode::Normal;
                                 if debug_mode {
-                                    debug_log.push("Cancelled task deletion".to_string());
+                                    debug_log.push("Day filter cleared".to_string());
                                 }
                             }
+                            KeyCode::Esc => app_mode = AppMode::Normal,
                             _ => {
                                 if debug_mode {
-                                    debug_log.push("Unhandled key in ConfirmingDelete mode".to_string());
+                                    debug_log.push("Unhandled key in ShowingCalendar mode".to_string());
                                 }
                             }
                         }
                     }
-                    AppMode::ShowingHelp => {
-                        // Any key closes help
+                    AppMode::ShowingStats => {
                         app_mode = AppMode::Normal;
                         if debug_mode {
-                            debug_log.push("Closed help".to_string());
+                            debug_log.push("Closed statistics".to_string());
                         }
                     }
                 }
+
+                if reload {
+                    // Saves already sent hold changes the user kept, so let them land first
+                    while saves_in_flight > 0 {
+                        let Ok(result) = result_rx.recv() else { break };
+                        saves_in_flight -= 1;
+                        save_error = result.err().map(|err| err.to_string());
+                    }
+                    dirty = reload_tasks(&mut tasks, &mut last_id, &mut selected, &mut load_warning);
+                    marked.clear();
+                    pending_block = None;
+                    last_deleted.clear();
+                    toast_message = Some(toast(format!("Reloaded {} task(s)", tasks.len())));
+                    if debug_mode {
+                        debug_log.push(format!("Reloaded todos.json from disk: {} task(s)", tasks.len()));
+                    }
+                }
             }
         }
+        Ok(())
+    }));
+
+    if let Some(log) = event_log.as_mut() {
+        let _ = log.record(&tasks);
+    }
+    let failures = finish_saves(save_tx, saver, &result_rx, (dirty && !read_only).then(|| (tasks.clone(), last_id)));
+    save_ui_state(&UiState {
+        selected_index: selected,
+        selected_id: tasks.get(selected).map(|task| task.id),
+        last_reset: ui_state.last_reset,
+    });
+    restore_terminal()?;
+    if let Some(path) = &listen_path {
+        let _ = std::fs::remove_file(path);
     }
+    for failure in failures {
+        eprintln!("gottodo: failed to save todos.json: {}", failure);
+    }
+    match outcome {
+        Ok(result) => result,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
 
-    terminal::disable_raw_mode()?;
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir, unique to the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("gottodo-main-test-{}-{}", process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// An environment holding only `vars`.
+    fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: HashMap<String, OsString> = vars.iter().map(|(name, value)| (name.to_string(), OsString::from(value))).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn config_path_flag_beats_everything() {
+        let dir = temp_dir("config-flag");
+        std::fs::write(dir.join("config.toml"), "").unwrap();
+        let env = vars(&[("GOTTODO_CONFIG", "/env.toml"), ("XDG_CONFIG_HOME", "/xdg")]);
+        assert_eq!(config_path(Some("/flag.toml"), env, &dir), (PathBuf::from("/flag.toml"), true));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_path_env_beats_the_current_directory() {
+        let dir = temp_dir("config-env");
+        std::fs::write(dir.join("config.toml"), "").unwrap();
+        let env = vars(&[("GOTTODO_CONFIG", "/env.toml"), ("XDG_CONFIG_HOME", "/xdg")]);
+        assert_eq!(config_path(None, env, &dir), (PathBuf::from("/env.toml"), true));
+        // Set but empty counts as unset
+        assert_eq!(config_path(None, vars(&[("GOTTODO_CONFIG", "")]), &dir), (dir.join("config.toml"), false));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_path_current_directory_beats_xdg() {
+        let dir = temp_dir("config-local");
+        std::fs::write(dir.join("config.toml"), "").unwrap();
+        let env = vars(&[("XDG_CONFIG_HOME", "/xdg"), ("HOME", "/home/me")]);
+        assert_eq!(config_path(None, env, &dir), (dir.join("config.toml"), false));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn config_path_falls_back_to_xdg_then_home() {
+        let dir = temp_dir("config-xdg");
+        let expected = PathBuf::from("/xdg/gottodo/config.toml");
+        assert_eq!(config_path(None, vars(&[("XDG_CONFIG_HOME", "/xdg"), ("HOME", "/home/me")]), &dir), (expected, false));
+        let expected = PathBuf::from("/home/me/.config/gottodo/config.toml");
+        assert_eq!(config_path(None, vars(&[("XDG_CONFIG_HOME", ""), ("HOME", "/home/me")]), &dir), (expected, false));
+        assert_eq!(config_path(None, vars(&[]), &dir), (dir.join("config.toml"), false));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn explicit_config_that_is_missing_is_reported() {
+        let dir = temp_dir("config-missing");
+        let (path, explicit) = config_path(None, vars(&[("GOTTODO_CONFIG", dir.join("nope.toml").to_str().unwrap())]), &dir);
+        let (_, warning) = load_config(&path, explicit);
+        assert!(warning.is_some());
+        let (path, explicit) = config_path(None, vars(&[]), &dir);
+        let (_, warning) = load_config(&path, explicit);
+        assert!(warning.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// `tasks` drawn by `list_items` into a `width`×`height` buffer.
+    fn draw_rows(tasks: &[Task], selected: usize, config: &Config, width: u16, height: u16) -> ratatui::buffer::Buffer {
+        let marked = HashSet::new();
+        let ctx = RowContext {
+            tasks,
+            config,
+            selected,
+            selected_group: None,
+            selected_in: None,
+            active_timer: None,
+            no_color: false,
+            snoozed_view: false,
+            show_details: false,
+            spacious: false,
+            now: Local::now(),
+            number_width: 1,
+            marked: &marked,
+        };
+        let rows: Vec<Row> = (0..tasks.len()).map(|i| Row::Task(i, Vec::new())).collect();
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|f| {
+                let area = f.size();
+                f.render_widget(List::new(list_items(&rows, area, &ctx)), area);
+            })
+            .unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// The text of row `y` of `buffer`, trailing blanks dropped.
+    fn row_text(buffer: &ratatui::buffer::Buffer, y: u16) -> String {
+        (0..buffer.area.width).map(|x| buffer.get(x, y).symbol()).collect::<String>().trim_end().to_string()
+    }
+
+    #[test]
+    fn inline_markup_is_off_by_default() {
+        assert!(!Config::default().inline_markup);
+        let tasks = vec![Task::new(1, "ship *it*")];
+        assert_eq!(row_text(&draw_rows(&tasks, 0, &Config::default(), 20, 1), 0), "[ ] ship *it*");
+    }
+
+    #[test]
+    fn markup_composes_with_the_selected_row() {
+        let config = Config { inline_markup: true, ..Config::default() };
+        let tasks = vec![Task::new(1, "ship *it*"), Task::new(2, "`code`")];
+        let buffer = draw_rows(&tasks, 0, &config, 20, 2);
+        assert_eq!(row_text(&buffer, 0), "[ ] ship it");
+        // The bold run keeps the selection's background
+        let bold = buffer.get(9, 0);
+        assert_eq!(bold.symbol(), "i");
+        assert!(bold.modifier.contains(Modifier::BOLD));
+        assert_eq!(bold.bg, Color::Blue);
+        assert!(!buffer.get(4, 0).modifier.contains(Modifier::BOLD));
+        assert_eq!(buffer.get(4, 0).bg, Color::Blue);
+        // Unselected code text is coloured without a background
+        assert_eq!(row_text(&buffer, 1), "[ ] code");
+        assert_eq!(buffer.get(4, 1).fg, Color::Cyan);
+        assert_eq!(buffer.get(4, 1).bg, Color::Reset);
+    }
+
+    #[test]
+    fn markup_composes_with_done_tasks() {
+        let config = Config { inline_markup: true, ..Config::default() };
+        let mut tasks = vec![Task::new(1, "_read_ it")];
+        tasks[0].toggle_done(Local::now());
+        let buffer = draw_rows(&tasks, 0, &config, 20, 1);
+        assert_eq!(row_text(&buffer, 0), "[x] read it");
+        assert!(buffer.get(4, 0).modifier.contains(Modifier::ITALIC));
+        assert_eq!(buffer.get(4, 0).bg, Color::Blue);
+        assert!(!buffer.get(9, 0).modifier.contains(Modifier::ITALIC));
+    }
+
+    /// A whole frame of `tasks` in Normal mode, after `tweak` adjusts the state.
+    fn draw_frame(tasks: &[Task], width: u16, height: u16, tweak: impl FnOnce(&mut FrameState)) -> ratatui::buffer::Buffer {
+        let config = Config::default();
+        let rows: Vec<Row> = (0..tasks.len()).map(|i| Row::Task(i, Vec::new())).collect();
+        let visible: Vec<usize> = (0..tasks.len()).collect();
+        let marked = HashSet::new();
+        let (mut help_scroll, mut help_height) = (0, 0);
+        let now = Local::now();
+        let mut state = FrameState {
+            tasks,
+            config: &config,
+            app_mode: &AppMode::Normal,
+            mini: false,
+            debug_mode: false,
+            ui_visible: true,
+            focus_mode: false,
+            no_color: false,
+            read_only: false,
+            rows: &rows,
+            other_rows: &[],
+            visible: &visible,
+            selected: 0,
+            selected_id: tasks.first().map(|task| task.id),
+            has_selection: !tasks.is_empty(),
+            selected_group: None,
+            selected_in: None,
+            active_timer: None,
+            snoozed_view: false,
+            show_details: false,
+            spacious: false,
+            group_view: false,
+            agenda_view: false,
+            overdue_view: false,
+            split_view: false,
+            focused_pane: Pane::Inbox,
+            sort_view: None,
+            category_filter: None,
+            day_filter: None,
+            search_query: "",
+            marked: &marked,
+            pending_block: None,
+            pending_delete: None,
+            pending_replace: None,
+            dirty: false,
+            saves_in_flight: 0,
+            load_warning: None,
+            save_error: None,
+            prompt_error: None,
+            pomodoro: None,
+            number_input: None,
+            typeahead: None,
+            toast: None,
+            add_due_today: false,
+            input_text: "",
+            now,
+            today: now.date_naive(),
+            calendar_day: now.date_naive(),
+            debug_log: &[],
+            help_scroll: &mut help_scroll,
+            help_height: &mut help_height,
+            editor: None,
+        };
+        tweak(&mut state);
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| render(f, &mut state)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// Every row of `buffer`, trailing blanks dropped.
+    fn frame_text(buffer: &ratatui::buffer::Buffer) -> Vec<String> {
+        (0..buffer.area.height).map(|y| row_text(buffer, y)).collect()
+    }
+
+    #[test]
+    fn normal_mode_frame() {
+        let mut tasks = vec![Task::new(1, "write report"), Task::new(2, "buy milk")];
+        tasks[1].done = true;
+        let frame = draw_frame(&tasks, 100, MIN_HEIGHT, |_| {});
+        let pad = " ".repeat(70);
+        assert_eq!(frame_text(&frame), [
+            format!("{}┌TODO — 1/2 done─────────────┐", pad),
+            format!("{}│[ ] write report            │", pad),
+            format!("{}│[x] buy milk                │", pad),
+            format!("{}│                            │", pad),
+            format!("{}│                            │", pad),
+            format!("{}└────────────────────────────┘", pad),
+            format!("{}{}", " ".repeat(30), key_hints(&AppMode::Normal)),
+        ]);
+        // The cursor row is the highlighted one
+        assert_eq!(frame.get(71, 1).bg, Color::Blue);
+        assert_eq!(frame.get(71, 2).bg, Color::Reset);
+    }
+
+    #[test]
+    fn too_small_terminal_shows_only_a_notice() {
+        let tasks = vec![Task::new(1, "write report")];
+        let frame = draw_frame(&tasks, MIN_WIDTH - 1, MIN_HEIGHT, |_| {});
+        assert_eq!(frame_text(&frame)[..2], ["Terminal too small (31x7), need", "32x7"]);
+        assert!(frame_text(&frame)[2..].iter().all(String::is_empty));
+        let frame = draw_frame(&tasks, 60, MIN_HEIGHT - 1, |_| {});
+        assert_eq!(row_text(&frame, 0), "Terminal too small (60x6), need 32x7");
+    }
+
+    #[test]
+    fn minimum_size_terminal_draws_the_list() {
+        let tasks = vec![Task::new(1, "write report")];
+        let frame = draw_frame(&tasks, MIN_WIDTH, MIN_HEIGHT, |_| {});
+        assert_eq!(row_text(&frame, 1), "  │[ ] write report            │");
+    }
+
+    #[test]
+    fn mini_mode_has_its_own_minimum() {
+        let tasks = vec![Task::new(1, "write report")];
+        let frame = draw_frame(&tasks, MINI_MIN_WIDTH, MINI_MIN_HEIGHT, |state| state.mini = true);
+        assert_eq!(row_text(&frame, 0), "[ ] write report");
+        let frame = draw_frame(&tasks, MINI_MIN_WIDTH, MINI_MIN_HEIGHT - 1, |state| state.mini = true);
+        assert_eq!(frame_text(&frame), ["Terminal too", "small (16x2),"]);
+    }
+
+    #[test]
+    fn delete_pending_removes_the_captured_task_where_it_now_is() {
+        let mut tasks = vec![Task::new(1, "a"), Task::new(2, "b"), Task::new(3, "c")];
+        let (mut selected, mut pending) = (1, Some(2));
+        // Reordered while the prompt was up
+        tasks.swap(1, 2);
+        let (index, task) = delete_pending(&mut tasks, &mut selected, &mut pending).unwrap();
+        assert_eq!((index, task.text.as_str()), (2, "b"));
+        assert_eq!(tasks.iter().map(|task| task.id).collect::<Vec<_>>(), [1, 3]);
+        assert_eq!((selected, pending), (1, None));
+    }
+
+    #[test]
+    fn delete_pending_after_the_list_emptied_deletes_nothing() {
+        let mut tasks = vec![Task::new(1, "a")];
+        let (mut selected, mut pending) = (0, Some(1));
+        tasks.clear();
+        assert!(delete_pending(&mut tasks, &mut selected, &mut pending).is_none());
+        assert_eq!((selected, pending), (0, None));
+        // A new task reusing the slot isn't mistaken for the old one
+        tasks.push(Task::new(4, "new"));
+        let mut pending = Some(1);
+        assert!(delete_pending(&mut tasks, &mut selected, &mut pending).is_none());
+        assert_eq!(tasks.len(), 1);
+    }
+
+    #[test]
+    fn delete_prompt_names_the_captured_task() {
+        let tasks = vec![Task::new(1, "a"), Task::new(2, "b")];
+        let prompt = |pending| {
+            let frame = draw_frame(&tasks, 100, 10, |state| {
+                state.app_mode = &AppMode::ConfirmingDelete;
+                state.pending_delete = pending;
+            });
+            // The prompt box sits above the status bar
+            row_text(&frame, 7).trim_end_matches(['│', ' ']).to_string()
+        };
+        assert_eq!(prompt(Some(2)), "│Delete 'b' ? (y/n)");
+        assert_eq!(prompt(Some(9)), "│No task to delete");
+    }
+
+    #[test]
+    fn exit_flushes_the_save_the_debounce_held_back() {
+        let dir = temp_dir("exit-flush");
+        let path = dir.join("todos.json");
+        let (save_tx, results, saver) = spawn_saver(FileStore::new(&path));
+        let mut tasks = vec![Task::new(1, "a")];
+        save_tx.send((tasks.clone(), 0)).unwrap();
+        // Changed again inside the debounce window, then quit
+        tasks.push(Task::new(2, "b"));
+        assert!(finish_saves(save_tx, saver, &results, Some((tasks, 0))).is_empty());
+        let saved: Vec<Task> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.iter().map(|task| task.text.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        assert!(!dir.join("todos.json.tmp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn exit_reports_saves_that_failed() {
+        let dir = temp_dir("exit-failure");
+        std::fs::write(dir.join("file"), "").unwrap();
+        let (save_tx, results, saver) = spawn_saver(FileStore::new(dir.join("file").join("todos.json")));
+        let failures = finish_saves(save_tx, saver, &results, Some((vec![Task::new(1, "a")], 1)));
+        assert_eq!(failures.len(), 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("gottodo").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn cli_without_a_subcommand_opens_the_list() {
+        let cli = parse(&["--debug", "--read-only", "--mini", "--filter", "#work", "--config", "c.toml"]).unwrap();
+        assert_eq!(cli.mode(), None);
+        assert!(cli.debug && cli.read_only && cli.mini);
+        assert_eq!(cli.filter.as_deref(), Some("#work"));
+        assert_eq!(cli.config.as_deref(), Some("c.toml"));
+        let cli = parse(&["--listen", "--serve", "8080"]).unwrap();
+        assert_eq!(cli.listen, Some(PathBuf::from("gottodo.sock")));
+        assert_eq!(cli.serve, Some(8080));
+        assert!(parse(&["--add"]).unwrap().add);
+    }
+
+    #[test]
+    fn cli_subcommands() {
+        assert_eq!(parse(&["stats"]).unwrap().mode(), Some(Mode::Stats));
+        assert_eq!(parse(&["status"]).unwrap().mode(), Some(Mode::Status));
+        assert_eq!(parse(&["replay-events"]).unwrap().mode(), Some(Mode::ReplayEvents));
+        assert_eq!(parse(&["journal"]).unwrap().mode(), Some(Mode::Journal { date: None }));
+        let date = NaiveDate::from_ymd_opt(2024, 5, 17);
+        assert_eq!(parse(&["journal", "--date", "2024-05-17"]).unwrap().mode(), Some(Mode::Journal { date }));
+        assert_eq!(parse(&["export-ics", "t.ics"]).unwrap().mode(), Some(Mode::ExportIcs { path: "t.ics".to_string() }));
+        assert_eq!(
+            parse(&["import-txt", "t.txt", "--replace", "--outline"]).unwrap().mode(),
+            Some(Mode::ImportTxt { path: "t.txt".to_string(), merge: false, replace: true, outline: true })
+        );
+        assert_eq!(parse(&["init", "--force"]).unwrap().mode(), Some(Mode::Init { force: true }));
+        // --config is shared, so init writes where it was pointed
+        let cli = parse(&["init", "--config", "c.toml"]).unwrap();
+        assert_eq!((cli.mode(), cli.config.as_deref()), (Some(Mode::Init { force: false }), Some("c.toml")));
+    }
+
+    #[test]
+    fn cli_old_flags_name_the_same_modes() {
+        let mode = |args: &[&str]| parse(args).unwrap().mode();
+        assert_eq!(mode(&["--stats"]), mode(&["stats"]));
+        assert_eq!(mode(&["--status"]), mode(&["status"]));
+        assert_eq!(mode(&["--replay-events"]), mode(&["replay-events"]));
+        assert_eq!(mode(&["--journal", "--date", "2024-05-17"]), mode(&["journal", "--date", "2024-05-17"]));
+        assert_eq!(mode(&["--export-ics", "t.ics"]), mode(&["export-ics", "t.ics"]));
+        assert_eq!(mode(&["--import-txt", "t.txt", "--merge", "--outline"]), mode(&["import-txt", "t.txt", "--merge", "--outline"]));
+        assert_eq!(mode(&["--init", "--force"]), mode(&["init", "--force"]));
+        // Old flags still carry the list options alongside, as they used to
+        assert_eq!(mode(&["--status", "--debug"]), Some(Mode::Status));
+        // but not their sub-options on their own
+        assert!(parse(&["--date", "2024-05-17"]).is_err());
+        assert!(parse(&["--force"]).is_err());
+    }
+
+    #[test]
+    fn cli_rejects_conflicting_and_misplaced_options() {
+        let kind = |args: &[&str]| parse(args).err().map(|err| err.kind());
+        assert_eq!(kind(&["--add", "--read-only"]), Some(clap::error::ErrorKind::ArgumentConflict));
+        assert_eq!(kind(&["import-txt", "t.txt", "--merge", "--replace"]), Some(clap::error::ErrorKind::ArgumentConflict));
+        // List options don't mix with a subcommand
+        assert!(parse(&["--debug", "stats"]).is_err());
+        assert!(parse(&["stats", "--debug"]).is_err());
+        // Options belong to their own subcommand
+        assert!(parse(&["stats", "--date", "2024-05-17"]).is_err());
+        assert!(parse(&["stats", "status"]).is_err());
+        assert!(parse(&["import-txt"]).is_err());
+        assert!(parse(&["journal", "--date", "tomorrow"]).is_err());
+        assert!(parse(&["--stats", "--status"]).is_err());
+        assert!(parse(&["--status", "status"]).is_err());
+    }
+
+    #[test]
+    fn report_tasks_leaves_the_disk_alone() {
+        let dir = temp_dir("report-tasks");
+        let path = dir.join("todos.json");
+        assert!(report_tasks(&path).is_empty());
+        assert!(!path.exists());
+        std::fs::write(&path, "not json").unwrap();
+        assert!(report_tasks(&path).is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not json");
+        assert!(!dir.join("todos.json.corrupt").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn quit_asks_while_a_timer_or_pomodoro_runs() {
+        let config = Config::default();
+        let timer = Some((1, Instant::now()));
+        let pomodoro = Pomodoro {
+            task_id: 1,
+            phase: PomodoroPhase::Work,
+            remaining: Duration::from_secs(60),
+            last_tick: Instant::now(),
+        };
+        assert!(!quit_needs_confirm(&config, None, None));
+        assert!(quit_needs_confirm(&config, timer, None));
+        assert!(quit_needs_confirm(&config, None, Some(&pomodoro)));
+        let config = Config { confirm_quit_with_timer: false, ..Config::default() };
+        assert!(!quit_needs_confirm(&config, timer, Some(&pomodoro)));
+    }
+
+    #[test]
+    fn startup_filter_prefers_a_category_then_a_tag() {
+        let tasks = vec![Task::new(1, "write report #work"), Task::new(2, "laundry #home")];
+        let tags = all_tags(&tasks);
+        let categories = vec!["home".to_string()];
+        assert_eq!(startup_filter("#home", &categories, &tags), Ok(StartupFilter::Category("home".to_string())));
+        assert_eq!(startup_filter("#work", &categories, &tags), Ok(StartupFilter::Search("#work".to_string())));
+        // The tag search narrows the list to the tagged task
+        let listed: Vec<usize> = fuzzy_rank(&tasks, "#work").into_iter().map(|(i, _)| i).collect();
+        assert_eq!(listed, [0]);
+        assert_eq!(startup_filter("report", &categories, &tags), Ok(StartupFilter::Search("report".to_string())));
+    }
+
+    #[test]
+    fn startup_filter_warns_about_a_name_nothing_uses() {
+        let tags = all_tags(&[Task::new(1, "write report #work")]);
+        let warning = startup_filter("#gym", &[], &tags).unwrap_err();
+        assert!(warning.contains("'gym'"), "{}", warning);
+    }
 }